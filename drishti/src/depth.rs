@@ -5,49 +5,137 @@ use pyo3::prelude::*;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use rppal::gpio::{Gpio, InputPin, OutputPin};
+use anyhow::{bail, Context, Result};
+use rppal::gpio::{Gpio, InputPin, Level, OutputPin};
 
-// ultrasonic pins
-const TRIG_PIN: u8 = 27; // D2 (robot-hat)
-const ECHO_PIN: u8 = 22; // D3 (robot-hat)
+use crate::error::{Error, PyError};
 
-#[pyclass]
-pub struct Ultrasonic {
+// default ultrasonic pins, see vahana::layout for rewired cars
+pub const TRIG_PIN: u8 = 27; // D2 (robot-hat)
+pub const ECHO_PIN: u8 = 22; // D3 (robot-hat)
+
+// longest echo is ~23 ms at the 4 m range, so a missing or unplugged sensor fails fast
+const ECHO_TIMEOUT: Duration = Duration::from_millis(40);
+
+// Trigger and echo lines, implemented by the rppal pins and by test doubles
+pub trait EchoPins: Send {
+    fn set_trigger(&mut self, level: Level);
+    fn echo(&self) -> Level;
+}
+
+struct GpioEchoPins {
     trig: OutputPin,
     echo: InputPin,
 }
 
+impl EchoPins for GpioEchoPins {
+    fn set_trigger(&mut self, level: Level) {
+        self.trig.write(level);
+    }
+
+    fn echo(&self) -> Level {
+        self.echo.read()
+    }
+}
+
+#[pyclass]
+pub struct Ultrasonic {
+    pins: Box<dyn EchoPins>,
+}
+
+impl Ultrasonic {
+    pub fn with_echo_pins(pins: Box<dyn EchoPins>) -> Self {
+        Self { pins }
+    }
+
+    pub fn with_pins(trig_pin: u8, echo_pin: u8) -> Result<Self> {
+        let gpio = Gpio::new().map_err(Error::from)?;
+        let trig = gpio
+            .get(trig_pin)
+            .map_err(Error::from)
+            .context("ULTRASONIC TRIG PIN INIT FAILED")?
+            .into_output();
+        let echo = gpio
+            .get(echo_pin)
+            .map_err(Error::from)
+            .context("ULTRASONIC ECHO PIN INIT FAILED")?
+            .into_input();
+
+        Ok(Ultrasonic::with_echo_pins(Box::new(GpioEchoPins {
+            trig,
+            echo,
+        })))
+    }
+
+    fn wait_for_echo(&self, level: Level) -> Result<()> {
+        let deadline = Instant::now() + ECHO_TIMEOUT;
+        while self.pins.echo() != level {
+            if Instant::now() >= deadline {
+                bail!(Error::Timeout(format!(
+                    "ULTRASONIC ECHO NEVER WENT {}",
+                    if level == Level::High { "HIGH" } else { "LOW" }
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[pymethods]
 impl Ultrasonic {
     #[new]
-    pub fn new() -> Result<Self> {
-        let trig = Gpio::new()?.get(TRIG_PIN)?.into_output();
-        let echo = Gpio::new()?.get(ECHO_PIN)?.into_input();
-
-        Ok(Ultrasonic { trig, echo })
+    pub fn new() -> Result<Self, PyError> {
+        Ok(Ultrasonic::with_pins(TRIG_PIN, ECHO_PIN)?)
     }
 
-    pub fn read(&mut self) -> u64 {
+    pub fn read(&mut self) -> Result<u64, PyError> {
         // Set trigger pin low for 5 us
-        self.trig.set_low();
+        self.pins.set_trigger(Level::Low);
         sleep(Duration::from_micros(5));
 
         // Generate a 10us pulse on trigger pin
-        self.trig.set_high();
+        self.pins.set_trigger(Level::High);
         sleep(Duration::from_micros(10));
-        self.trig.set_low();
+        self.pins.set_trigger(Level::Low);
 
         // Wait for the echo pin to go high
-        while !self.echo.is_high() {}
+        self.wait_for_echo(Level::High)?;
 
         let pulse_start = Instant::now();
         // Wait for the echo pin to go low
-        while !self.echo.is_low() {}
+        self.wait_for_echo(Level::Low)?;
 
         // Distance in cm
         let time_taken = pulse_start.elapsed().as_micros();
 
-        (time_taken / 58) as u64
+        Ok((time_taken / 58) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StuckEcho(Level);
+
+    impl EchoPins for StuckEcho {
+        fn set_trigger(&mut self, _level: Level) {}
+
+        fn echo(&self) -> Level {
+            self.0
+        }
+    }
+
+    #[test]
+    fn stuck_echo_times_out() {
+        for level in [Level::Low, Level::High] {
+            let mut ultrasonic = Ultrasonic::with_echo_pins(Box::new(StuckEcho(level)));
+
+            let started = Instant::now();
+            let error = ultrasonic.read().unwrap_err();
+            assert!(matches!(error.kind(), Some(Error::Timeout(_))));
+            assert!(started.elapsed() < ECHO_TIMEOUT * 3);
+        }
     }
 }
@@ -0,0 +1,177 @@
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+
+use std::fmt;
+
+use thiserror::Error as ThisError;
+
+// Root causes shared by drishti and vahana. Raise them with `bail!(Error::...)`
+// or `.map_err(Error::from)` and keep adding `.context(...)` on top as usual
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("I2C BUS ERROR")]
+    Bus(#[from] rppal::i2c::Error),
+    #[error("NO I2C DEVICE AT 0x{0:02x}")]
+    DeviceNotFound(u16),
+    #[error("GPIO ERROR")]
+    Gpio(#[from] rppal::gpio::Error),
+    #[error("TIMED OUT: {0}")]
+    Timeout(String),
+    #[error("INVALID ARGUMENT: {0}")]
+    InvalidArgument(String),
+    #[error("CALIBRATION ERROR: {0}")]
+    Calibration(String),
+    #[error("CONFIG ERROR: {0}")]
+    Config(String),
+    #[error("CONFIG PARSE ERROR")]
+    Parse(#[from] toml::de::Error),
+    #[error("I/O ERROR")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Error::InvalidArgument(message.into())
+    }
+}
+
+// Python exception hierarchy, one class per variant under a common base
+create_exception!(ruspy, PicarError, PyException);
+create_exception!(ruspy, BusError, PicarError);
+create_exception!(ruspy, DeviceNotFoundError, BusError);
+create_exception!(ruspy, GpioError, PicarError);
+create_exception!(ruspy, TimeoutError, PicarError);
+create_exception!(ruspy, InvalidArgumentError, PicarError);
+create_exception!(ruspy, CalibrationError, PicarError);
+create_exception!(ruspy, ConfigError, PicarError);
+create_exception!(ruspy, IoError, PicarError);
+
+pub fn register_exceptions(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("PicarError", py.get_type::<PicarError>())?;
+    m.add("BusError", py.get_type::<BusError>())?;
+    m.add("DeviceNotFoundError", py.get_type::<DeviceNotFoundError>())?;
+    m.add("GpioError", py.get_type::<GpioError>())?;
+    m.add("TimeoutError", py.get_type::<TimeoutError>())?;
+    m.add(
+        "InvalidArgumentError",
+        py.get_type::<InvalidArgumentError>(),
+    )?;
+    m.add("CalibrationError", py.get_type::<CalibrationError>())?;
+    m.add("ConfigError", py.get_type::<ConfigError>())?;
+    m.add("IoError", py.get_type::<IoError>())?;
+
+    Ok(())
+}
+
+// Error type of everything exposed to Python: an anyhow chain that is raised
+// as the exception class of its outermost typed cause, with the full chain as message
+pub struct PyError(anyhow::Error);
+
+impl PyError {
+    pub fn kind(&self) -> Option<&Error> {
+        find_kind(&self.0)
+    }
+
+    pub fn into_inner(self) -> anyhow::Error {
+        self.0
+    }
+}
+
+fn find_kind(error: &anyhow::Error) -> Option<&Error> {
+    error.chain().find_map(|cause| {
+        cause
+            .downcast_ref::<Error>()
+            .or_else(|| cause.downcast_ref::<PyError>().and_then(PyError::kind))
+    })
+}
+
+impl From<anyhow::Error> for PyError {
+    fn from(error: anyhow::Error) -> Self {
+        Self(error)
+    }
+}
+
+impl From<Error> for PyError {
+    fn from(error: Error) -> Self {
+        Self(error.into())
+    }
+}
+
+impl fmt::Debug for PyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for PyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// Lets Rust callers keep using `?` and `.context()` on Python facing methods
+impl std::error::Error for PyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl From<PyError> for PyErr {
+    fn from(error: PyError) -> Self {
+        let message = format!("{:#}", error.0);
+        match error.kind() {
+            Some(Error::Bus(_)) => BusError::new_err(message),
+            Some(Error::DeviceNotFound(_)) => DeviceNotFoundError::new_err(message),
+            Some(Error::Gpio(_)) => GpioError::new_err(message),
+            Some(Error::Timeout(_)) => TimeoutError::new_err(message),
+            Some(Error::InvalidArgument(_)) => InvalidArgumentError::new_err(message),
+            Some(Error::Calibration(_)) => CalibrationError::new_err(message),
+            Some(Error::Config(_) | Error::Parse(_)) => ConfigError::new_err(message),
+            Some(Error::Io(_)) => IoError::new_err(message),
+            None => PicarError::new_err(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::{bail, Context, Result};
+
+    fn out_of_range() -> Result<()> {
+        bail!(Error::invalid("CHANNEL 14 OUT OF RANGE"))
+    }
+
+    fn python_facing() -> std::result::Result<(), PyError> {
+        out_of_range().context("PWM INIT FAILED")?;
+        Ok(())
+    }
+
+    #[test]
+    fn kind_is_found_under_context() {
+        let error = PyError::from(out_of_range().context("SERVO INIT FAILED").unwrap_err());
+
+        assert!(matches!(error.kind(), Some(Error::InvalidArgument(_))));
+        assert_eq!(
+            format!("{:#}", error.into_inner()),
+            "SERVO INIT FAILED: INVALID ARGUMENT: CHANNEL 14 OUT OF RANGE"
+        );
+    }
+
+    #[test]
+    fn kind_survives_a_rust_caller_wrapping_a_python_facing_error() {
+        let error: anyhow::Error = python_facing().context("STEERING INIT FAILED").unwrap_err();
+
+        assert!(matches!(find_kind(&error), Some(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn io_and_untyped_errors() {
+        let io = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let error = PyError::from(anyhow::Error::from(Error::from(io)).context("READING LAYOUT"));
+        assert!(matches!(error.kind(), Some(Error::Io(_))));
+
+        let plain = PyError::from(anyhow::anyhow!("SOMETHING ELSE"));
+        assert!(plain.kind().is_none());
+    }
+}
@@ -0,0 +1,299 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, PyError};
+
+// grayscale module channels (robot-hat)
+pub const GRAYSCALE_CHANNELS: [u8; 3] = [0, 1, 2]; // A0 (left), A1 (middle), A2 (right)
+const POSITIONS: [f64; 3] = [-1.0, 0.0, 1.0];
+
+// Raw analog source for the sensors, implemented by vahana::adc::Adc
+pub trait AnalogReader: Send {
+    fn read_channels(&mut self, channels: &[u8]) -> Result<Vec<u16>>;
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrayscaleStatus {
+    Floor,
+    Line,
+    Cliff, // nothing reflected, e.g. a table edge
+}
+
+// Per-surface references, one value per sensor (left, middle, right)
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrayscaleCalibration {
+    #[pyo3(get, set)]
+    pub floor: [u16; 3],
+    #[pyo3(get, set)]
+    pub line: [u16; 3],
+    #[pyo3(get, set)]
+    pub cliff: [u16; 3], // readings below this are a cliff
+}
+
+// The picar-x line reference is a single threshold of 1000 (darker is line). A floor at
+// 2000 and a line at 0 put the 0.5 weight of line_weight() on that same 1000; its
+// cliff reference 200 is kept as is
+impl Default for GrayscaleCalibration {
+    fn default() -> Self {
+        Self {
+            floor: [2000; 3],
+            line: [0; 3],
+            cliff: [200; 3],
+        }
+    }
+}
+
+#[pymethods]
+impl GrayscaleCalibration {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn classify(&self, readings: [u16; 3]) -> [GrayscaleStatus; 3] {
+        let mut status = [GrayscaleStatus::Floor; 3];
+        for (i, &value) in readings.iter().enumerate() {
+            status[i] = if value < self.cliff[i] {
+                GrayscaleStatus::Cliff
+            } else if self.line_weight(i, value) > 0.5 {
+                GrayscaleStatus::Line
+            } else {
+                GrayscaleStatus::Floor
+            };
+        }
+
+        status
+    }
+
+    // -1.0 (under the left sensor) .. 1.0 (under the right sensor), None if no line or a cliff
+    pub fn line_position(&self, readings: [u16; 3]) -> Option<f64> {
+        if self.classify(readings).contains(&GrayscaleStatus::Cliff) {
+            return None;
+        }
+
+        let weights: Vec<f64> = (0..3).map(|i| self.line_weight(i, readings[i])).collect();
+        let total: f64 = weights.iter().sum();
+        if total < 0.5 {
+            return None;
+        }
+
+        Some(
+            weights
+                .iter()
+                .zip(POSITIONS)
+                .map(|(w, x)| w * x)
+                .sum::<f64>()
+                / total,
+        )
+    }
+}
+
+impl GrayscaleCalibration {
+    // 0.0 at the floor reference, 1.0 at the line reference (works for either contrast)
+    fn line_weight(&self, i: usize, value: u16) -> f64 {
+        let (floor, line) = (self.floor[i] as f64, self.line[i] as f64);
+        if floor == line {
+            return 0.0;
+        }
+
+        ((floor - value as f64) / (floor - line)).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CalibrationFile {
+    #[serde(default)]
+    surfaces: BTreeMap<String, GrayscaleCalibration>,
+}
+
+fn read_calibration_file(path: &Path) -> Result<CalibrationFile> {
+    if !path.exists() {
+        return Ok(CalibrationFile::default());
+    }
+
+    let text = fs::read_to_string(path)
+        .map_err(Error::from)
+        .with_context(|| format!("READING GRAYSCALE CALIBRATION {}", path.display()))?;
+    toml::from_str(&text)
+        .map_err(Error::from)
+        .with_context(|| format!("PARSING GRAYSCALE CALIBRATION {}", path.display()))
+}
+
+#[pyclass]
+pub struct Grayscale {
+    reader: Box<dyn AnalogReader>,
+    channels: [u8; 3],
+    #[pyo3(get, set)]
+    pub calibration: GrayscaleCalibration,
+}
+
+impl Grayscale {
+    pub fn with_reader(reader: Box<dyn AnalogReader>, channels: [u8; 3]) -> Self {
+        Self {
+            reader,
+            channels,
+            calibration: GrayscaleCalibration::default(),
+        }
+    }
+
+    fn average(&mut self, samples: u32) -> Result<[u16; 3]> {
+        if samples == 0 {
+            bail!(Error::invalid(
+                "GRAYSCALE CALIBRATION NEEDS AT LEAST ONE SAMPLE"
+            ));
+        }
+
+        let mut sum = [0u32; 3];
+        for _ in 0..samples {
+            let readings = self.read()?;
+            for (total, value) in sum.iter_mut().zip(readings) {
+                *total += value as u32;
+            }
+        }
+
+        Ok(sum.map(|total| (total / samples) as u16))
+    }
+}
+
+#[pymethods]
+impl Grayscale {
+    pub fn read(&mut self) -> Result<[u16; 3], PyError> {
+        let readings = self
+            .reader
+            .read_channels(&self.channels)
+            .context("GRAYSCALE READ FAILED")?;
+
+        let readings = readings
+            .try_into()
+            .map_err(|_| anyhow!("GRAYSCALE READ RETURNED WRONG CHANNEL COUNT"))?;
+
+        Ok(readings)
+    }
+
+    pub fn read_status(&mut self) -> Result<[GrayscaleStatus; 3], PyError> {
+        let readings = self.read()?;
+        Ok(self.calibration.classify(readings))
+    }
+
+    pub fn line_position(&mut self) -> Result<Option<f64>, PyError> {
+        let readings = self.read()?;
+        Ok(self.calibration.line_position(readings))
+    }
+
+    // Place all three sensors over bare floor
+    pub fn calibrate_floor(&mut self, samples: u32) -> Result<[u16; 3], PyError> {
+        self.calibration.floor = self.average(samples)?;
+        Ok(self.calibration.floor)
+    }
+
+    // Place all three sensors over the line
+    pub fn calibrate_line(&mut self, samples: u32) -> Result<[u16; 3], PyError> {
+        self.calibration.line = self.average(samples)?;
+        Ok(self.calibration.line)
+    }
+
+    // Stores the current calibration as `surface`, keeping other surfaces in the file
+    pub fn save_calibration(&self, path: &str, surface: &str) -> Result<(), PyError> {
+        let path = Path::new(path);
+        let mut file = read_calibration_file(path)?;
+        file.surfaces.insert(surface.to_string(), self.calibration);
+
+        let text = toml::to_string(&file).context("SERIALISING GRAYSCALE CALIBRATION")?;
+        fs::write(path, text)
+            .map_err(Error::from)
+            .with_context(|| format!("WRITING GRAYSCALE CALIBRATION {}", path.display()))?;
+
+        Ok(())
+    }
+
+    pub fn load_calibration(&mut self, path: &str, surface: &str) -> Result<(), PyError> {
+        let file = read_calibration_file(Path::new(path))?;
+        match file.surfaces.get(surface) {
+            Some(calibration) => self.calibration = *calibration,
+            None => {
+                return Err(Error::Calibration(format!(
+                    "NO GRAYSCALE CALIBRATION FOR SURFACE {surface:?} IN {path}"
+                ))
+                .into())
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Canned(Arc<Mutex<[u16; 3]>>);
+
+    impl AnalogReader for Canned {
+        fn read_channels(&mut self, channels: &[u8]) -> Result<Vec<u16>> {
+            let values = *self.0.lock().unwrap();
+            Ok(channels.iter().map(|&c| values[c as usize]).collect())
+        }
+    }
+
+    fn calibrated() -> GrayscaleCalibration {
+        GrayscaleCalibration {
+            floor: [1600, 1500, 1400],
+            line: [300, 300, 300],
+            cliff: [100, 100, 100],
+        }
+    }
+
+    #[test]
+    fn classify_line_floor_and_cliff() {
+        use GrayscaleStatus::*;
+
+        let calibration = calibrated();
+        assert_eq!(calibration.classify([1550, 350, 50]), [Floor, Line, Cliff]);
+    }
+
+    #[test]
+    fn line_position_is_weighted_between_sensors() {
+        let calibration = calibrated();
+
+        assert_eq!(calibration.line_position([1600, 300, 1400]), Some(0.0));
+        assert_eq!(calibration.line_position([300, 1500, 1400]), Some(-1.0));
+        let between = calibration.line_position([1400, 300, 300]).unwrap();
+        assert!((0.35..0.45).contains(&between));
+        assert_eq!(calibration.line_position([1600, 1500, 1400]), None);
+        assert_eq!(calibration.line_position([300, 300, 50]), None);
+    }
+
+    #[test]
+    fn calibration_records_and_round_trips_surfaces() {
+        let reader = Canned::default();
+        let mut grayscale = Grayscale::with_reader(Box::new(reader.clone()), GRAYSCALE_CHANNELS);
+
+        *reader.0.lock().unwrap() = [1500, 1450, 1400];
+        grayscale.calibrate_floor(4).unwrap();
+        *reader.0.lock().unwrap() = [200, 250, 300];
+        grayscale.calibrate_line(4).unwrap();
+
+        let path = std::env::temp_dir().join(format!("grayscale-{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        grayscale.save_calibration(path, "track").unwrap();
+        grayscale.calibration = GrayscaleCalibration::default();
+        grayscale.load_calibration(path, "track").unwrap();
+        let missing = grayscale.load_calibration(path, "carpet");
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(grayscale.calibration.floor, [1500, 1450, 1400]);
+        assert_eq!(grayscale.calibration.line, [200, 250, 300]);
+        assert!(missing.is_err());
+    }
+}
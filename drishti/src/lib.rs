@@ -1,3 +1,5 @@
 pub mod depth;
+pub mod error;
 pub mod eyes;
+pub mod grayscale;
 pub mod lane;
@@ -2,85 +2,136 @@
 
 use pyo3::prelude::*;
 
-use std::thread::sleep;
-use std::time::Duration;
-
 use anyhow::{Context, Result};
-use rppal::gpio::Gpio;
-
-use drishti::depth::Ultrasonic;
+use drishti::{
+    depth::Ultrasonic,
+    error::{register_exceptions, PyError},
+};
 use vahana::{
-    drive::{Motors, Servo},
-    init_i2c,
+    board,
+    drive::{
+        Motors, MotorsCalibration, Servo, ServosCalibration, MOTOR_CALIBRATION_PATH,
+        SERVO_CALIBRATION_PATH, SERVO_FREQ,
+    },
+    hat::RobotHat,
+    layout::{self, HardwareLayout, LAYOUT_PATH},
+    shutdown,
 };
 
-// const BOARD_TYPE: u8 = 12;
-
-// fn check_board_type() -> Result<bool> {
-//     let type_pin = Gpio::new()?.get(BOARD_TYPE)?.into_input();
-
-//     Ok(type_pin.is_low())
-// }
-
+// `board` ("v1" / "v2") overrides BOARD_TYPE detection for the rest of the process
 #[pyfunction]
-fn reset_mcu() -> Result<()> {
-    // We already know reset pin is 5 -> so skipping functions
-    // let rst_pin: u8 = if check_board_type().expect("Error checking board type") {
-    //     21
-    // } else {
-    //     5
-    // };
-    let mut rst_pin = Gpio::new()?.get(5)?.into_output();
-
-    rst_pin.set_low();
-    sleep(Duration::from_millis(1));
-    rst_pin.set_high();
-    sleep(Duration::from_millis(1));
+fn reset_mcu(board: Option<&str>) -> Result<(), PyError> {
+    let profile = board::select(board).context("BOARD PROFILE UNAVAILABLE")?;
+    profile.reset_mcu()
+}
 
-    Ok(())
+// Loads the hardware layout (default LAYOUT_PATH, built-in PiCar-X wiring if missing)
+#[pyfunction]
+pub fn layout_init(path: Option<&str>) -> Result<HardwareLayout, PyError> {
+    Ok(layout::load(path.unwrap_or(LAYOUT_PATH), None).context("HARDWARE LAYOUT UNAVAILABLE")?)
 }
 
 #[pyfunction]
-pub fn main_init() -> Result<()> {
+pub fn main_init(board: Option<&str>) -> Result<(), PyError> {
+    // LOAD LAYOUT (an explicit `board` wins over the file, and over detection)
+    layout::load(LAYOUT_PATH, board).context("HARDWARE LAYOUT UNAVAILABLE")?;
     // RESET MCU
-    reset_mcu().context("MCU RESET UNSUCCESSFULL [BEGIN]")?;
+    reset_mcu(board).context("MCU RESET UNSUCCESSFULL [BEGIN]")?;
     // INIT I2C
-    init_i2c().context("I2C INITIALIZATION FAILED")?;
+    RobotHat::shared()
+        .and_then(|hat| Ok(hat.init()?))
+        .context("I2C INITIALIZATION FAILED")?;
 
     Ok(())
 }
 
+// Applies the servo calibration file (default SERVO_CALIBRATION_PATH, uncalibrated if missing)
 #[pyfunction]
-pub fn servos_init(init_angles: [i32; 3]) -> Result<[Servo; 3]> {
-    let mut camera_servo_pin1 = Servo::new(0).context("camera_servo_pin1 init failed")?; // P0
-    let mut camera_servo_pin2 = Servo::new(1).context("camera_servo_pin2 init failed")?; // P1
-    let mut dir_servo_pin = Servo::new(2).context("dir_servo_pin init failed")?; // P2
-    camera_servo_pin1.angle(init_angles[0]);
-    camera_servo_pin2.angle(init_angles[1]);
-    dir_servo_pin.angle(init_angles[2]);
+pub fn servos_init(
+    init_angles: [i32; 3],
+    calibration: Option<&str>,
+) -> Result<[Servo; 3], PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let pins = board::current()?.pins;
+    let layout = layout::current().servos;
+    let calibration =
+        ServosCalibration::load_or_default(calibration.unwrap_or(SERVO_CALIBRATION_PATH))
+            .context("SERVO CALIBRATION UNAVAILABLE")?;
+    let servo = |name: &str| -> Result<Servo> {
+        let servo = Servo::with_pwm(hat.pwm(pins.pwm(name)?.index())?, SERVO_FREQ)?;
+        calibration.apply(&servo)?;
+        Ok(servo)
+    };
+    let camera_servo_pin1 = servo(&layout.camera_pan).context("camera_servo_pin1 init failed")?;
+    let camera_servo_pin2 = servo(&layout.camera_tilt).context("camera_servo_pin2 init failed")?;
+    let dir_servo_pin = servo(&layout.steering).context("dir_servo_pin init failed")?;
+    camera_servo_pin1.angle(init_angles[0])?;
+    camera_servo_pin2.angle(init_angles[1])?;
+    dir_servo_pin.angle(init_angles[2])?;
 
     Ok([camera_servo_pin1, camera_servo_pin2, dir_servo_pin])
 }
 
+// Applies the motor calibration file (default MOTOR_CALIBRATION_PATH, uncalibrated if missing)
 #[pyfunction]
-pub fn motors_init(period: u16, prescaler: u16) -> Result<Motors> {
-    let mut motors = Motors::new().context("motors init failed")?;
-    // set period and prescaler for motors
-    motors.left_motor.pwm.period(period)?;
-    motors.left_motor.pwm.prescaler(prescaler)?;
-    motors.right_motor.pwm.period(period)?;
-    motors.right_motor.pwm.prescaler(prescaler)?;
+pub fn motors_init(freq: f64, calibration: Option<&str>) -> Result<Motors, PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let motors = Motors::with_hat(&hat, freq).context("motors init failed")?;
+    let calibration =
+        MotorsCalibration::load_or_default(calibration.unwrap_or(MOTOR_CALIBRATION_PATH))
+            .context("MOTOR CALIBRATION UNAVAILABLE")?;
+    motors.set_calibration(calibration)?;
 
     Ok(motors)
 }
 
+// Zero duty on every motor and park angle on every servo still alive
 #[pyfunction]
-pub fn ultrasonic_init() -> Result<Ultrasonic> {
-    let ultrasonic = Ultrasonic::new().context("context")?;
+pub fn park_all() -> Result<(), PyError> {
+    Ok(shutdown::park_all()?)
+}
+
+// Parks everything on Ctrl-C / SIGTERM. Ctrl-C still raises KeyboardInterrupt afterwards,
+// SIGTERM exits the process
+#[pyfunction]
+pub fn install_shutdown_hook() -> Result<(), PyError> {
+    Ok(shutdown::install()?)
+}
+
+#[pyfunction]
+pub fn ultrasonic_init() -> Result<Ultrasonic, PyError> {
+    let pins = board::current()?.pins;
+    let layout = layout::current().sensors;
+    let ultrasonic = Ultrasonic::with_pins(
+        pins.gpio(&layout.ultrasonic_trig)?,
+        pins.gpio(&layout.ultrasonic_echo)?,
+    )
+    .context("ULTRASONIC INIT FAILED")?;
 
     Ok(ultrasonic)
 }
 
+// Written out like ruspy's, so `except dust.BusError` works too
+#[pymodule]
+pub fn dust(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(reset_mcu, m)?)?;
+    m.add_function(wrap_pyfunction!(layout_init, m)?)?;
+    m.add_function(wrap_pyfunction!(main_init, m)?)?;
+    m.add_function(wrap_pyfunction!(servos_init, m)?)?;
+    m.add_function(wrap_pyfunction!(motors_init, m)?)?;
+    m.add_function(wrap_pyfunction!(park_all, m)?)?;
+    m.add_function(wrap_pyfunction!(install_shutdown_hook, m)?)?;
+    m.add_function(wrap_pyfunction!(ultrasonic_init, m)?)?;
+
+    // the classes the functions above return
+    m.add_class::<HardwareLayout>()?;
+    m.add_class::<Servo>()?;
+    m.add_class::<Motors>()?;
+    m.add_class::<Ultrasonic>()?;
+
+    register_exceptions(py, m)
+}
+
 pub fn scratchpad() -> Result<()> {
     // cv_example_vid().context("something in video failed")?;
 
@@ -108,34 +159,3 @@ pub fn scratchpad() -> Result<()> {
 
     Ok(())
 }
-/*
-===============================================
-PIN REFERENCES FROM ROBOT-HAT (BCM NUMBERS)
-===============================================
-    "D0":  17
-    "D1":   4
-    "D2":  27
-    "D3":  22
-    "D4":  23
-    "D5":  24
-    "D6":  25
-    "D7":   4
-    "D8":   5
-    "D9":   6
-    "D10": 12
-    "D11": 13
-    "D12": 19
-    "D13": 16
-    "D14": 26
-    "D15": 20
-    "D16": 21
-    "SW":  25
-    "USER": 25
-    "LED": 26
-    "BOARD_TYPE": 12
-    "RST": 16
-    "BLEINT": 13
-    "BLERST": 20
-    "MCURST":  5
-===============================================
-*/
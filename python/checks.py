@@ -13,24 +13,27 @@ import ruspy
 def us_check():
     us = ruspy.ultrasonic_init()
     for _ in range(5):
-        distance = us.read()
-        print(f"Distance: {distance} cm")
+        try:
+            print(f"Distance: {us.read()} cm")
+        except ruspy.TimeoutError as e:
+            print(f"No echo: {e}")
         # Sleep for 60 milliseconds (as per DATASHEET) --> FIX ME: consider ultrasonic.read() timing into account
         time.sleep(0.06)
 
 
 # Motors example check
 def motors_check():
-    motors = ruspy.motors_init(50, 100)
-    motors.forward(10)
-    time.sleep(3)
-    motors.turn_left(5)
-    time.sleep(3)
-    motors.turn_right(15)
-    time.sleep(3)
-    motors.backward(20)
-    time.sleep(3)
-    motors.stop()
+    # emergency stop on leaving the block, also on exceptions
+    with ruspy.motors_init(14400) as motors:
+        motors.forward(10)
+        time.sleep(3)
+        motors.turn_left(5)
+        time.sleep(3)
+        motors.turn_right(15)
+        time.sleep(3)
+        motors.backward(20)
+        time.sleep(3)
+        motors.stop()
 
 
 # Servos example check
@@ -38,12 +41,14 @@ def servos_check():
     camera_servo_pin1, camera_servo_pin2, dir_servo_pin = ruspy.servos_init(
         [10, 20, 30]
     )
-    camera_servo_pin1.angle(90)
-    time.sleep(1)
-    camera_servo_pin2.angle(90)
-    time.sleep(1)
-    dir_servo_pin.angle(90)
-    time.sleep(1)
+    # each servo returns to its park angle (0 by default) on leaving the block
+    with camera_servo_pin1, camera_servo_pin2, dir_servo_pin:
+        camera_servo_pin1.angle(90)
+        time.sleep(1)
+        camera_servo_pin2.angle(90)
+        time.sleep(1)
+        dir_servo_pin.angle(90)
+        time.sleep(1)
 
 
 # Cameras example check
@@ -109,10 +114,12 @@ def try_func(func):
         traceback.print_exc()
     finally:
         print("FINAL RESET")
+        ruspy.park_all()
         ruspy.reset_mcu()
 
 
 def checks():
+    ruspy.install_shutdown_hook()
     try_func(us_check)
     try_func(motors_check)
     try_func(servos_check)
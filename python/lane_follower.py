@@ -12,11 +12,14 @@ theta = 0
 minLineLength = 5
 maxLineGap = 10
 
+# motor PWM frequency (Hz), shared by every runner
+MOTOR_FREQ = 14400
+
 
 def run_robot(secs=10):
     started = time.time()
     vid_cap = create_video_capture(640, 480, 30)
-    motors = ruspy.motors_init(50, 100)
+    motors = ruspy.motors_init(MOTOR_FREQ)
     motors.speed(100, 100)
     # motors.forward(100)
     # time.sleep(0.5)
@@ -64,7 +67,7 @@ def run_robot_with_nn(secs=10):
     started = time.time()
     vid_cap = create_video_capture(640, 480, 30)
     ld = LaneDetector(image_width=640, image_height=480)
-    motors = ruspy.motors_init(50, 100)
+    motors = ruspy.motors_init(MOTOR_FREQ)
     motors.speed(100, 100)
     # motors.forward(100)
     # time.sleep(0.5)
@@ -114,6 +117,58 @@ def run_robot_with_nn(secs=10):
     motors.stop()
 
 
+CONTROLLERS = {
+    "pid": ruspy.PidController,
+    "pure_pursuit": ruspy.PurePursuit,
+    "stanley": ruspy.Stanley,
+}
+
+
+def run_robot_with_controller(controller="stanley", speed=40, secs=10, search=True):
+    started = time.time()
+    vid_cap = create_video_capture(640, 480, 30)
+    ld = LaneDetector(image_width=640, image_height=480)
+    steering = CONTROLLERS[controller]()
+
+    with ruspy.vehicle_init(MOTOR_FREQ) as vehicle, ruspy.neck_init() as neck:
+        # pans towards a missing line without holding up the loop
+        lane_search = ruspy.LaneSearch(neck) if search else None
+        odometry = vehicle.enable_odometry()
+        # ground speed (m/s) for the controllers that adapt to it
+        velocity = odometry.config.velocity(speed)
+        last = time.time()
+
+        while (time.time() - started) < secs:
+            # where the camera pointed for this frame, it may still be turning
+            pan = neck.pose.pan
+            ret, frame = vid_cap.read()
+            if not ret:
+                print("FRAME NOT CAPTURED")
+                continue
+            left_poly, right_poly, _, _ = ld(frame)
+            now = time.time()
+            dt, last = now - last, now
+
+            left_poly, right_poly = list(left_poly), list(right_poly)
+            if lane_search is not None:
+                view = lane_search.update(left_poly, right_poly, pan)
+                left_poly, right_poly = view.left, view.right
+
+            path = ruspy.centre_line(left_poly, right_poly)
+            if path is None:
+                print("NO LANE DETECTED")
+                steering.reset()
+                if lane_search is not None:
+                    lane_search.reset()
+                vehicle.stop()
+                continue
+
+            angle = steering.steer(path, velocity, dt)
+            vehicle.drive_angle(speed, angle)
+
+        print("STOPPING", odometry.pose)
+
+
 if __name__ == "__main__":
     ruspy.main_init()
     try_func(run_robot_with_nn)
@@ -0,0 +1,13 @@
+// The module is built with pyo3's extension-module feature, which leaves libpython
+// to the interpreter loading it. tests/ embed Python instead, so only they link it
+fn main() {
+    let config = pyo3_build_config::get();
+    if let Some(lib_dir) = &config.lib_dir {
+        println!("cargo:rustc-link-arg-tests=-L{lib_dir}");
+        println!("cargo:rustc-link-arg-tests=-Wl,-rpath,{lib_dir}");
+    }
+    if let Some(lib_name) = &config.lib_name {
+        println!("cargo:rustc-link-arg-tests=-l{lib_name}");
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
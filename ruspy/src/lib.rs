@@ -1,76 +1,317 @@
 // rustimport:pyo3
 
-pub mod depth;
-pub mod drive;
-pub mod i2c_pwm;
-
 use pyo3::prelude::*;
 
-use std::thread::sleep;
-use std::time::Duration;
-
 use anyhow::{Context, Result};
-use rppal::gpio::Gpio;
-
-use depth::Ultrasonic;
-use drive::{Motors, Servo};
-use i2c_pwm::init_i2c;
+use drishti::{
+    depth::Ultrasonic,
+    error::{register_exceptions, PyError},
+    grayscale::{Grayscale, GrayscaleCalibration, GrayscaleStatus},
+};
+use vahana::{
+    adc::{Adc, AdcCalibration},
+    axel::{self, LaneConfig, LaneLines, LaneSteering},
+    battery::{BatteryConfig, BatteryMonitor, BatteryState},
+    board::{self, BoardProfile, BoardRevision, Peripheral},
+    control::{PidController, PurePursuit, Stanley},
+    drive::{
+        Motor, MotorCalibration, Motors, MotorsCalibration, RampConfig, Servo, ServoCalibration,
+        ServosCalibration, MOTOR_CALIBRATION_PATH, SERVO_CALIBRATION_PATH, SERVO_FREQ,
+    },
+    hat::{RecoveryEvent, RecoveryKind, RetryPolicy, RobotHat},
+    layout::{self, HardwareLayout, MotorLayout, SensorLayout, ServoLayout, LAYOUT_PATH},
+    motion::{Motion, MotionEvent, MotionExecutor, MotionState},
+    neck::{LaneSearch, LaneSearchConfig, LaneSearchPhase, LaneView, Neck, NeckConfig, NeckPose},
+    odometry::{Odometry, OdometryConfig, Pose},
+    pins::PinMap,
+    scan::{I2cDevice, ProbeMode},
+    shutdown,
+    timing::PwmTiming,
+    vehicle::{AckermannMix, Vehicle, VehicleGeometry},
+    watchdog::WatchdogStatus,
+    MyI2c, PWM,
+};
 
+// `board` ("v1" / "v2") overrides BOARD_TYPE detection for the rest of the process
 #[pyfunction]
-fn reset_mcu() -> Result<()> {
-    // We already know reset pin is 5 -> so skipping functions
-    // let rst_pin: u8 = if check_board_type().expect("Error checking board type") {
-    //     21
-    // } else {
-    //     5
-    // };
-    let mut rst_pin = Gpio::new()?.get(5)?.into_output();
+fn reset_mcu(board: Option<&str>) -> Result<(), PyError> {
+    let profile = board::select(board).context("BOARD PROFILE UNAVAILABLE")?;
+    profile.reset_mcu()
+}
 
-    rst_pin.set_low();
-    sleep(Duration::from_millis(1));
-    rst_pin.set_high();
-    sleep(Duration::from_millis(1));
+#[pyfunction]
+pub fn board_init(board: Option<&str>) -> Result<BoardProfile, PyError> {
+    Ok(board::select(board).context("BOARD PROFILE UNAVAILABLE")?)
+}
 
-    Ok(())
+// Loads the hardware layout (default LAYOUT_PATH, built-in PiCar-X wiring if missing)
+#[pyfunction]
+pub fn layout_init(path: Option<&str>) -> Result<HardwareLayout, PyError> {
+    Ok(layout::load(path.unwrap_or(LAYOUT_PATH), None).context("HARDWARE LAYOUT UNAVAILABLE")?)
 }
 
 #[pyfunction]
-pub fn main_init() -> Result<()> {
+pub fn main_init(board: Option<&str>) -> Result<(), PyError> {
+    // LOAD LAYOUT (an explicit `board` wins over the file, and over detection)
+    layout::load(LAYOUT_PATH, board).context("HARDWARE LAYOUT UNAVAILABLE")?;
     // RESET MCU
-    reset_mcu().context("MCU RESET UNSUCCESSFULL [BEGIN]")?;
+    reset_mcu(board).context("MCU RESET UNSUCCESSFULL [BEGIN]")?;
     // INIT I2C
-    init_i2c().context("I2C INITIALIZATION FAILED")?;
+    RobotHat::shared()
+        .and_then(|hat| Ok(hat.init()?))
+        .context("I2C INITIALIZATION FAILED")?;
 
     Ok(())
 }
 
+// Applies the servo calibration file (default SERVO_CALIBRATION_PATH, uncalibrated if missing)
 #[pyfunction]
-pub fn servos_init(init_angles: [i32; 3]) -> Result<[Servo; 3]> {
-    let mut camera_servo_pin1 = Servo::new(0).context("camera_servo_pin1 init failed")?; // P0
-    let mut camera_servo_pin2 = Servo::new(1).context("camera_servo_pin2 init failed")?; // P1
-    let mut dir_servo_pin = Servo::new(2).context("dir_servo_pin init failed")?; // P2
-    camera_servo_pin1.angle(init_angles[0]);
-    camera_servo_pin2.angle(init_angles[1]);
-    dir_servo_pin.angle(init_angles[2]);
+pub fn servos_init(
+    init_angles: [i32; 3],
+    calibration: Option<&str>,
+) -> Result<[Servo; 3], PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let pins = board::current()?.pins;
+    let layout = layout::current().servos;
+    let calibration =
+        ServosCalibration::load_or_default(calibration.unwrap_or(SERVO_CALIBRATION_PATH))
+            .context("SERVO CALIBRATION UNAVAILABLE")?;
+    let servo = |name: &str| -> Result<Servo> {
+        let servo = Servo::with_pwm(hat.pwm(pins.pwm(name)?.index())?, SERVO_FREQ)?;
+        calibration.apply(&servo)?;
+        Ok(servo)
+    };
+    let camera_servo_pin1 = servo(&layout.camera_pan).context("camera_servo_pin1 init failed")?;
+    let camera_servo_pin2 = servo(&layout.camera_tilt).context("camera_servo_pin2 init failed")?;
+    let dir_servo_pin = servo(&layout.steering).context("dir_servo_pin init failed")?;
+    camera_servo_pin1.angle(init_angles[0])?;
+    camera_servo_pin2.angle(init_angles[1])?;
+    dir_servo_pin.angle(init_angles[2])?;
 
     Ok([camera_servo_pin1, camera_servo_pin2, dir_servo_pin])
 }
 
+// Applies the motor calibration file (default MOTOR_CALIBRATION_PATH, uncalibrated if missing)
 #[pyfunction]
-pub fn motors_init(period: u16, prescaler: u16) -> Result<Motors> {
-    let mut motors = Motors::new().context("motors init failed")?;
-    // set period and prescaler for motors
-    motors.left_motor.pwm.period(period)?;
-    motors.left_motor.pwm.prescaler(prescaler)?;
-    motors.right_motor.pwm.period(period)?;
-    motors.right_motor.pwm.prescaler(prescaler)?;
+pub fn motors_init(freq: f64, calibration: Option<&str>) -> Result<Motors, PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let motors = Motors::with_hat(&hat, freq).context("motors init failed")?;
+    let calibration =
+        MotorsCalibration::load_or_default(calibration.unwrap_or(MOTOR_CALIBRATION_PATH))
+            .context("MOTOR CALIBRATION UNAVAILABLE")?;
+    motors.set_calibration(calibration)?;
 
     Ok(motors)
 }
 
+// Steering servo and rear motors from the layout, with both calibration files applied
+#[pyfunction]
+pub fn vehicle_init(
+    freq: f64,
+    calibration: Option<&str>,
+    servo_calibration: Option<&str>,
+) -> Result<Vehicle, PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let vehicle = Vehicle::with_hat(&hat, freq).context("VEHICLE INIT FAILED")?;
+    let calibration =
+        MotorsCalibration::load_or_default(calibration.unwrap_or(MOTOR_CALIBRATION_PATH))
+            .context("MOTOR CALIBRATION UNAVAILABLE")?;
+    vehicle.motors().set_calibration(calibration)?;
+    let servo_calibration =
+        ServosCalibration::load_or_default(servo_calibration.unwrap_or(SERVO_CALIBRATION_PATH))
+            .context("SERVO CALIBRATION UNAVAILABLE")?;
+    servo_calibration.apply(&vehicle.steering())?;
+
+    Ok(vehicle)
+}
+
+// Camera pan and tilt servos from the layout, with the servo calibration file applied
+#[pyfunction]
+pub fn neck_init(calibration: Option<&str>) -> Result<Neck, PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let neck = Neck::with_hat(&hat).context("NECK INIT FAILED")?;
+    let calibration =
+        ServosCalibration::load_or_default(calibration.unwrap_or(SERVO_CALIBRATION_PATH))
+            .context("SERVO CALIBRATION UNAVAILABLE")?;
+    calibration.apply(&neck.pan_servo())?;
+    calibration.apply(&neck.tilt_servo())?;
+    // the neck starts parked, again through the calibration
+    neck.park()?;
+
+    Ok(neck)
+}
+
+// Shared HAT handle, e.g. for `retry` and `take_events()`
+#[pyfunction]
+pub fn hat_init() -> Result<RobotHat, PyError> {
+    Ok(RobotHat::shared().context("I2C INITIALIZATION FAILED")?)
+}
+
+#[pyfunction]
+pub fn adc_init() -> Result<Adc, PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+
+    Ok(hat.adc())
+}
+
+#[pyfunction]
+pub fn battery_init(config: Option<BatteryConfig>) -> Result<BatteryMonitor, PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let config = match config {
+        Some(config) => config,
+        None => BatteryConfig::from_layout()?,
+    };
+
+    Ok(BatteryMonitor::with_adc(hat.adc(), config)?)
+}
+
 #[pyfunction]
-pub fn ultrasonic_init() -> Result<Ultrasonic> {
-    let ultrasonic = Ultrasonic::new().context("context")?;
+pub fn grayscale_init() -> Result<Grayscale, PyError> {
+    let hat = RobotHat::shared().context("I2C INITIALIZATION FAILED")?;
+    let pins = board::current()?.pins;
+    let [left, middle, right] = layout::current().sensors.grayscale;
+    let channels = [
+        pins.analog(&left)?.index(),
+        pins.analog(&middle)?.index(),
+        pins.analog(&right)?.index(),
+    ];
+
+    Ok(Grayscale::with_reader(Box::new(hat.adc()), channels))
+}
+
+// Zero duty on every motor and park angle on every servo still alive
+#[pyfunction]
+pub fn park_all() -> Result<(), PyError> {
+    Ok(shutdown::park_all()?)
+}
+
+// Parks everything on Ctrl-C / SIGTERM. Ctrl-C still raises KeyboardInterrupt afterwards,
+// SIGTERM exits the process
+#[pyfunction]
+pub fn install_shutdown_hook() -> Result<(), PyError> {
+    Ok(shutdown::install()?)
+}
+
+#[pyfunction]
+pub fn ultrasonic_init() -> Result<Ultrasonic, PyError> {
+    let pins = board::current()?.pins;
+    let layout = layout::current().sensors;
+    let ultrasonic = Ultrasonic::with_pins(
+        pins.gpio(&layout.ultrasonic_trig)?,
+        pins.gpio(&layout.ultrasonic_echo)?,
+    )
+    .context("ULTRASONIC INIT FAILED")?;
 
     Ok(ultrasonic)
 }
+
+#[pyfunction]
+pub fn scan_i2c(mode: Option<&str>) -> Result<Vec<I2cDevice>, PyError> {
+    let mode = mode.map(str::parse).transpose()?;
+    vahana::scan::scan_i2c(mode)
+}
+
+// Steering for the lane polynomials (highest power first, e.g. `list(left_poly)`),
+// None when neither line was seen
+#[pyfunction]
+pub fn required_angle(
+    left: Vec<f64>,
+    right: Vec<f64>,
+    config: Option<LaneConfig>,
+) -> Option<LaneSteering> {
+    axel::required_angle(&left, &right, &config.unwrap_or_default())
+}
+
+// Centre of the lane as a polynomial, the path the steering controllers follow
+#[pyfunction]
+#[pyo3(signature = (left, right, lane_width = LaneConfig::default().lane_width))]
+pub fn centre_line(left: Vec<f64>, right: Vec<f64>, lane_width: f64) -> Option<Vec<f64>> {
+    axel::centre_line(&left, &right, lane_width).map(|(centre, _)| centre)
+}
+
+// Written out instead of rustimport's generated module, so the exception
+// classes are importable too (`except ruspy.BusError`). Add new pyfunctions and
+// pyclasses here, tests/module.rs fails on a missing class
+#[pymodule]
+pub fn ruspy(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(reset_mcu, m)?)?;
+    m.add_function(wrap_pyfunction!(board_init, m)?)?;
+    m.add_function(wrap_pyfunction!(layout_init, m)?)?;
+    m.add_function(wrap_pyfunction!(main_init, m)?)?;
+    m.add_function(wrap_pyfunction!(servos_init, m)?)?;
+    m.add_function(wrap_pyfunction!(motors_init, m)?)?;
+    m.add_function(wrap_pyfunction!(vehicle_init, m)?)?;
+    m.add_function(wrap_pyfunction!(neck_init, m)?)?;
+    m.add_function(wrap_pyfunction!(hat_init, m)?)?;
+    m.add_function(wrap_pyfunction!(adc_init, m)?)?;
+    m.add_function(wrap_pyfunction!(battery_init, m)?)?;
+    m.add_function(wrap_pyfunction!(grayscale_init, m)?)?;
+    m.add_function(wrap_pyfunction!(ultrasonic_init, m)?)?;
+    m.add_function(wrap_pyfunction!(park_all, m)?)?;
+    m.add_function(wrap_pyfunction!(install_shutdown_hook, m)?)?;
+    m.add_function(wrap_pyfunction!(scan_i2c, m)?)?;
+    m.add_function(wrap_pyfunction!(required_angle, m)?)?;
+    m.add_function(wrap_pyfunction!(centre_line, m)?)?;
+
+    // every #[pyclass] of vahana and drishti, see tests/module.rs
+    m.add_class::<BoardProfile>()?;
+    m.add_class::<BoardRevision>()?;
+    m.add_class::<Peripheral>()?;
+    m.add_class::<PinMap>()?;
+    m.add_class::<HardwareLayout>()?;
+    m.add_class::<MotorLayout>()?;
+    m.add_class::<ServoLayout>()?;
+    m.add_class::<SensorLayout>()?;
+    m.add_class::<RobotHat>()?;
+    m.add_class::<RetryPolicy>()?;
+    m.add_class::<RecoveryEvent>()?;
+    m.add_class::<RecoveryKind>()?;
+    m.add_class::<MyI2c>()?;
+    m.add_class::<PWM>()?;
+    m.add_class::<PwmTiming>()?;
+    m.add_class::<ProbeMode>()?;
+    m.add_class::<I2cDevice>()?;
+    m.add_class::<Adc>()?;
+    m.add_class::<AdcCalibration>()?;
+    m.add_class::<BatteryState>()?;
+    m.add_class::<BatteryConfig>()?;
+    m.add_class::<BatteryMonitor>()?;
+    m.add_class::<Grayscale>()?;
+    m.add_class::<GrayscaleCalibration>()?;
+    m.add_class::<GrayscaleStatus>()?;
+    m.add_class::<Ultrasonic>()?;
+    m.add_class::<Servo>()?;
+    m.add_class::<ServoCalibration>()?;
+    m.add_class::<ServosCalibration>()?;
+    m.add_class::<Motor>()?;
+    m.add_class::<MotorCalibration>()?;
+    m.add_class::<Motors>()?;
+    m.add_class::<MotorsCalibration>()?;
+    m.add_class::<RampConfig>()?;
+    m.add_class::<WatchdogStatus>()?;
+    m.add_class::<Vehicle>()?;
+    m.add_class::<VehicleGeometry>()?;
+    m.add_class::<AckermannMix>()?;
+    m.add_class::<Motion>()?;
+    m.add_class::<MotionEvent>()?;
+    m.add_class::<MotionExecutor>()?;
+    m.add_class::<MotionState>()?;
+    m.add_class::<Odometry>()?;
+    m.add_class::<OdometryConfig>()?;
+    m.add_class::<Pose>()?;
+    m.add_class::<LaneConfig>()?;
+    m.add_class::<LaneLines>()?;
+    m.add_class::<LaneSteering>()?;
+    m.add_class::<PidController>()?;
+    m.add_class::<PurePursuit>()?;
+    m.add_class::<Stanley>()?;
+    m.add_class::<Neck>()?;
+    m.add_class::<NeckConfig>()?;
+    m.add_class::<NeckPose>()?;
+    m.add_class::<LaneSearch>()?;
+    m.add_class::<LaneSearchConfig>()?;
+    m.add_class::<LaneSearchPhase>()?;
+    m.add_class::<LaneView>()?;
+
+    register_exceptions(py, m)
+}
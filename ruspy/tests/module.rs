@@ -0,0 +1,58 @@
+use std::{fs, path::Path};
+
+use pyo3::{prelude::*, types::IntoPyDict};
+
+// Names of the `#[pyclass]` items in the .rs files under `dir`
+fn pyclasses(dir: &Path) -> Vec<String> {
+    let mut names = vec![];
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "rs") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let mut lines = source.lines();
+        while let Some(line) = lines.next() {
+            if !line.trim_start().starts_with("#[pyclass") {
+                continue;
+            }
+            let item = lines
+                .by_ref()
+                .map(str::trim)
+                .find(|line| line.starts_with("pub struct") || line.starts_with("pub enum"))
+                .unwrap();
+            let name = item.split_whitespace().nth(2).unwrap();
+            names.push(name.trim_end_matches(['{', '(', ';']).to_string());
+        }
+    }
+    names
+}
+
+#[test]
+fn every_pyclass_is_registered() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut classes = pyclasses(&root.join("vahana/src"));
+    classes.extend(pyclasses(&root.join("drishti/src")));
+    assert!(classes.len() > 50, "{classes:?}");
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let module = pyo3::wrap_pymodule!(ruspy::ruspy)(py);
+        let module = module.as_ref(py);
+        let missing: Vec<&String> = classes
+            .iter()
+            .filter(|class| !module.hasattr(class.as_str()).unwrap())
+            .collect();
+        assert!(missing.is_empty(), "NOT REGISTERED: {missing:?}");
+
+        // enums compare with their module attributes, configs build from Python
+        for code in [
+            "assert ruspy.BatteryState.Cutoff == ruspy.BatteryState.Cutoff",
+            "ruspy.RetryPolicy(3, 10)",
+            "ruspy.BusError",
+        ] {
+            py.run(code, Some([("ruspy", module)].into_py_dict(py)), None)
+                .unwrap_or_else(|e| panic!("{code}: {e}"));
+        }
+    });
+}
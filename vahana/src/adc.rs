@@ -0,0 +1,203 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use anyhow::{Context, Result};
+use drishti::grayscale::AnalogReader;
+
+use crate::{
+    error::PyError,
+    hal::I2cBus,
+    hat::RobotHat,
+    protocol::{decode_word, AdcChannel, Command, ADC_CHANNELS},
+};
+
+pub const ADC_MAX: u16 = 4095; // 12 bit
+pub const ADC_VREF: f64 = 3.3;
+
+// voltage = raw * ADC_VREF / ADC_MAX * gain + offset
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdcCalibration {
+    #[pyo3(get, set)]
+    pub gain: f64,
+    #[pyo3(get, set)]
+    pub offset: f64,
+}
+
+impl Default for AdcCalibration {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+#[pymethods]
+impl AdcCalibration {
+    #[new]
+    #[pyo3(signature = (gain = 1.0, offset = 0.0))]
+    pub fn new(gain: f64, offset: f64) -> Self {
+        Self { gain, offset }
+    }
+
+    pub fn voltage(&self, raw: u16) -> f64 {
+        raw as f64 * ADC_VREF / ADC_MAX as f64 * self.gain + self.offset
+    }
+}
+
+// One conversion: select the channel with a word write, then read MSB and LSB
+fn read_raw(bus: &mut dyn I2cBus, channel: AdcChannel) -> Result<u16> {
+    let index = channel.index();
+    Command::adc_select(channel)
+        .send(bus)
+        .with_context(|| format!("ADC A{index} SELECT FAILED"))?;
+    let msb = bus
+        .smbus_receive_byte()
+        .with_context(|| format!("ADC A{index} READ FAILED"))?;
+    let lsb = bus
+        .smbus_receive_byte()
+        .with_context(|| format!("ADC A{index} READ FAILED"))?;
+
+    Ok(decode_word([msb, lsb]))
+}
+
+// Analog inputs A0-A7 of the Robot HAT MCU
+#[pyclass]
+#[derive(Clone)]
+pub struct Adc {
+    hat: RobotHat,
+    calibration: [AdcCalibration; ADC_CHANNELS as usize],
+}
+
+impl Adc {
+    pub fn with_hat(hat: RobotHat) -> Self {
+        Self {
+            hat,
+            calibration: [AdcCalibration::default(); ADC_CHANNELS as usize],
+        }
+    }
+}
+
+#[pymethods]
+impl Adc {
+    #[new]
+    pub fn new() -> Result<Self, PyError> {
+        let hat = RobotHat::shared().context("ADC I2C INIT FAILED")?;
+        Ok(Adc::with_hat(hat))
+    }
+
+    pub fn calibrate(&mut self, channel: u8, calibration: AdcCalibration) -> Result<(), PyError> {
+        let channel = AdcChannel::new(channel)?;
+        self.calibration[channel.index() as usize] = calibration;
+
+        Ok(())
+    }
+
+    pub fn calibration(&self, channel: u8) -> Result<AdcCalibration, PyError> {
+        let channel = AdcChannel::new(channel)?;
+
+        Ok(self.calibration[channel.index() as usize])
+    }
+
+    pub fn read(&self, channel: u8) -> Result<u16, PyError> {
+        let channel = AdcChannel::new(channel)?;
+        Ok(self.hat.lock().transact(|bus| read_raw(bus, channel))?)
+    }
+
+    pub fn read_voltage(&self, channel: u8) -> Result<f64, PyError> {
+        let raw = self.read(channel)?;
+
+        Ok(self.calibration[channel as usize].voltage(raw))
+    }
+
+    // Converts all `channels` back to back, without other bus traffic in between
+    pub fn read_many(&self, channels: Vec<u8>) -> Result<Vec<u16>, PyError> {
+        let channels = channels
+            .into_iter()
+            .map(AdcChannel::new)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut hat = self.hat.lock();
+        let raw = channels
+            .into_iter()
+            .map(|channel| hat.transact(|bus| read_raw(bus, channel)))
+            .collect::<Result<_>>()?;
+
+        Ok(raw)
+    }
+
+    pub fn read_voltages(&self, channels: Vec<u8>) -> Result<Vec<f64>, PyError> {
+        let raw = self.read_many(channels.clone())?;
+
+        Ok(channels
+            .iter()
+            .zip(raw)
+            .map(|(&channel, raw)| self.calibration[channel as usize].voltage(raw))
+            .collect())
+    }
+}
+
+impl AnalogReader for Adc {
+    fn read_channels(&mut self, channels: &[u8]) -> Result<Vec<u16>> {
+        Ok(self.read_many(channels.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockBus;
+
+    const ADDR: u16 = 0x14;
+
+    fn mock_adc() -> (Adc, MockBus) {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        (Adc::with_hat(hat), mock)
+    }
+
+    #[test]
+    fn read_selects_reversed_channel_register() {
+        let (adc, mock) = mock_adc();
+        mock.set_response(ADDR, 0x17, &[0x04, 0xD2]);
+        mock.set_response(ADDR, 0x10, &[0x0F, 0xFF]);
+
+        assert_eq!(adc.read(0).unwrap(), 1234);
+        assert_eq!(mock.last_word_write().unwrap().register, 0x17);
+        assert_eq!(adc.read(7).unwrap(), 4095);
+        assert_eq!(mock.last_word_write().unwrap().register, 0x10);
+    }
+
+    #[test]
+    fn voltages_use_channel_calibration() {
+        let (mut adc, mock) = mock_adc();
+        mock.set_response(ADDR, 0x13, &ADC_MAX.to_be_bytes()); // A4
+        adc.calibrate(4, AdcCalibration::new(3.0, 0.1)).unwrap();
+
+        let voltage = adc.read_voltage(4).unwrap();
+        assert!((voltage - (3.3 * 3.0 + 0.1)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn batch_reads_keep_channel_order() {
+        let (adc, mock) = mock_adc();
+        for (channel, raw) in [(0u8, 100u16), (1, 2000), (2, 4000)] {
+            mock.set_response(ADDR, 0x17 - channel, &raw.to_be_bytes());
+        }
+
+        assert_eq!(adc.read_many(vec![2, 0, 1]).unwrap(), vec![4000, 100, 2000]);
+        let volts = adc.read_voltages(vec![1]).unwrap();
+        assert!((volts[0] - 2000.0 * 3.3 / 4095.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn out_of_range_channels_are_rejected() {
+        let (mut adc, _) = mock_adc();
+
+        assert!(adc.read(8).is_err());
+        assert!(adc.read_many(vec![0, 8]).is_err());
+        assert!(adc.calibrate(8, AdcCalibration::default()).is_err());
+    }
+}
@@ -1,10 +1,268 @@
-// ToDo: output of curve fitting funtion from lane rs -> coefficients have to be used here
-
-pub fn required_angle() {
-    /*1. because these are parallel lines, the coeeficients of the polynomils should be equal, and only the offsets(constants) should have some difference
-    2. you can subtract the polynomials and get the distance between the 2 lines(width of the lane)
-    3. now take the distance, devide it by 2, and replace it with the offset of any one of the polynomial
-    4. now you got the polynomial which will pass from the center of the lane
-    5. subtract the center from the image center to get the deviation
-    6. based on the deviation (- or + and value), call the servo apis accordingly*/
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use crate::vehicle::VehicleGeometry;
+
+// Lane lines come as polynomials y = p(x) in the road frame of the camera
+// (x ahead, y to the left, metres), highest power first, e.g. the cubic fits
+// of LaneDetector.fit_poly. All zero coefficients mean the line was not seen
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaneConfig {
+    #[pyo3(get, set)]
+    pub lane_width: f64, // assumed when only one line is seen (m)
+    #[pyo3(get, set)]
+    pub lookahead: f64, // distance ahead the steering aims at (m)
+    #[pyo3(get, set)]
+    pub geometry: VehicleGeometry,
+}
+
+// Tape lanes sized for the PiCar-X
+impl Default for LaneConfig {
+    fn default() -> Self {
+        Self {
+            lane_width: 0.3,
+            lookahead: 0.25,
+            geometry: VehicleGeometry::default(),
+        }
+    }
+}
+
+#[pymethods]
+impl LaneConfig {
+    #[new]
+    #[pyo3(signature = (lane_width = 0.3, lookahead = 0.25, geometry = None))]
+    pub fn new(lane_width: f64, lookahead: f64, geometry: Option<VehicleGeometry>) -> Self {
+        Self {
+            lane_width,
+            lookahead,
+            geometry: geometry.unwrap_or_default(),
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaneLines {
+    Both,
+    Left,  // right line missing, the centre is half a lane width right of this one
+    Right, // left line missing
+}
+
+// Signs follow the steering servo: positive means the lane centre is to the right
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaneSteering {
+    #[pyo3(get)]
+    pub offset: f64, // lane centre relative to the camera, across the car (m)
+    #[pyo3(get)]
+    pub heading_error: f64, // lane direction relative to the car (deg)
+    #[pyo3(get)]
+    pub curvature: f64, // of the arc through the lookahead point (1/m)
+    #[pyo3(get)]
+    pub steering: f64, // front wheel angle for the P2 direction servo (deg)
+    #[pyo3(get)]
+    pub lines: LaneLines,
+}
+
+#[pymethods]
+impl LaneSteering {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+pub fn missing(poly: &[f64]) -> bool {
+    poly.iter().all(|&c| c == 0.0)
+}
+
+// p(x), Horner's rule
+pub fn eval(poly: &[f64], x: f64) -> f64 {
+    poly.iter().fold(0.0, |y, &c| y * x + c)
+}
+
+// p'(x)
+pub fn slope(poly: &[f64], x: f64) -> f64 {
+    let degree = poly.len().saturating_sub(1);
+    poly.iter()
+        .take(degree)
+        .enumerate()
+        .fold(0.0, |y, (i, &c)| y * x + c * (degree - i) as f64)
+}
+
+// Least squares polynomial through `points`, None when they don't pin one down
+pub fn fit(points: &[(f64, f64)], degree: usize) -> Option<Vec<f64>> {
+    let n = degree + 1;
+    // normal equations, powers highest first like the coefficients
+    let mut a = vec![vec![0.0; n + 1]; n];
+    for &(x, y) in points {
+        let powers: Vec<f64> = (0..n).map(|i| x.powi((degree - i) as i32)).collect();
+        for (row, &p) in a.iter_mut().zip(&powers) {
+            for (cell, &q) in row.iter_mut().zip(&powers) {
+                *cell += p * q;
+            }
+            row[n] += p * y;
+        }
+    }
+
+    // Gaussian elimination with partial pivoting
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        let pivot = a[col].clone();
+        for (i, row) in a.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col] / pivot[col];
+                for (cell, &p) in row.iter_mut().zip(&pivot).skip(col) {
+                    *cell -= factor * p;
+                }
+            }
+        }
+    }
+
+    Some((0..n).map(|i| a[i][n] / a[i][i]).collect())
+}
+
+// Mean of two polynomials, aligned on the constant term (np.poly1d drops leading zeros)
+fn mean(left: &[f64], right: &[f64]) -> Vec<f64> {
+    let len = left.len().max(right.len());
+    let coefficient = |poly: &[f64], power: usize| {
+        poly.len()
+            .checked_sub(power + 1)
+            .map_or(0.0, |index| poly[index])
+    };
+    (0..len)
+        .rev()
+        .map(|power| (coefficient(left, power) + coefficient(right, power)) / 2.0)
+        .collect()
+}
+
+// Same shape, moved sideways by `dy` (positive to the left)
+fn shifted(poly: &[f64], dy: f64) -> Vec<f64> {
+    let mut poly = if poly.is_empty() {
+        vec![0.0]
+    } else {
+        poly.to_vec()
+    };
+    *poly.last_mut().unwrap() += dy;
+    poly
+}
+
+// Lane lines are parallel, so the centre line is their mean, or one line moved by
+// half a lane width. None when neither line was seen
+pub fn centre_line(left: &[f64], right: &[f64], lane_width: f64) -> Option<(Vec<f64>, LaneLines)> {
+    match (missing(left), missing(right)) {
+        (false, false) => Some((mean(left, right), LaneLines::Both)),
+        (false, true) => Some((shifted(left, -lane_width / 2.0), LaneLines::Left)),
+        (true, false) => Some((shifted(right, lane_width / 2.0), LaneLines::Right)),
+        (true, true) => None,
+    }
+}
+
+// The steering follows the arc from the camera through the centre line at the
+// lookahead distance (pure pursuit)
+pub fn required_angle(left: &[f64], right: &[f64], config: &LaneConfig) -> Option<LaneSteering> {
+    let (centre, lines) = centre_line(left, right, config.lane_width)?;
+
+    let ahead = config.lookahead;
+    let aside = eval(&centre, ahead);
+    // the arc bends left for a point on the left, steering signs are the other way round
+    let curvature = -2.0 * aside / (ahead * ahead + aside * aside);
+
+    Some(LaneSteering {
+        offset: -eval(&centre, 0.0),
+        heading_error: -slope(&centre, 0.0).atan().to_degrees(),
+        curvature,
+        steering: config.geometry.steering_angle(curvature),
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn centred_straight_lane_needs_no_steering() {
+        let left = [0.0, 0.0, 0.0, 0.15];
+        let right = [0.0, 0.0, 0.0, -0.15];
+        let steering = required_angle(&left, &right, &LaneConfig::default()).unwrap();
+
+        assert_eq!(steering.lines, LaneLines::Both);
+        assert!(close(steering.offset, 0.0) && close(steering.heading_error, 0.0));
+        assert!(close(steering.steering, 0.0));
+    }
+
+    #[test]
+    fn offset_steers_back_to_the_centre() {
+        // the car sits 0.1 m left of the centre line
+        let left = [0.0, 0.0, 0.0, 0.05];
+        let right = [-0.25]; // np.poly1d strips the zero coefficients
+        let steering = required_angle(&left, &right, &LaneConfig::default()).unwrap();
+
+        assert!(close(steering.offset, 0.1));
+        let kappa = 2.0 * 0.1 / (0.25f64.powi(2) + 0.1f64.powi(2));
+        assert!(close(steering.curvature, kappa));
+        assert!(close(
+            steering.steering,
+            (0.095 * kappa).atan().to_degrees()
+        ));
+
+        // tighter than the servo allows when aiming close
+        let close_in = LaneConfig::new(0.3, 0.1, None);
+        let clamped = required_angle(&left, &right, &close_in).unwrap();
+        assert_eq!(clamped.steering, 30.0);
+    }
+
+    #[test]
+    fn lane_turning_left_gives_negative_heading_and_steering() {
+        let left = [0.0, 0.0, 0.2, 0.15];
+        let right = [0.0, 0.0, 0.2, -0.15];
+        let steering = required_angle(&left, &right, &LaneConfig::default()).unwrap();
+
+        assert!(close(steering.offset, 0.0));
+        assert!(close(steering.heading_error, -0.2f64.atan().to_degrees()));
+        assert!(steering.steering < 0.0);
+    }
+
+    #[test]
+    fn polynomials_evaluate_highest_power_first() {
+        let poly = [2.0, -1.0, 0.5, 3.0]; // 2x^3 - x^2 + 0.5x + 3
+        assert!(close(eval(&poly, 2.0), 16.0 - 4.0 + 1.0 + 3.0));
+        assert!(close(slope(&poly, 2.0), 24.0 - 4.0 + 0.5));
+        assert_eq!(slope(&[3.0], 1.0), 0.0);
+        assert_eq!(eval(&[], 1.0), 0.0);
+
+        let points: Vec<(f64, f64)> = (0..10)
+            .map(|i| (i as f64 * 0.1, eval(&poly, i as f64 * 0.1)))
+            .collect();
+        let fitted = fit(&points, 3).unwrap();
+        assert!(fitted.iter().zip(poly).all(|(&a, b)| close(a, b)));
+        assert_eq!(fit(&[(1.0, 2.0), (1.0, 3.0)], 1), None);
+    }
+
+    #[test]
+    fn single_line_is_moved_by_half_a_lane() {
+        let config = LaneConfig::default();
+        let both = required_angle(&[0.5, -0.1, 0.05, 0.15], &[0.5, -0.1, 0.05, -0.15], &config);
+        let left = required_angle(&[0.5, -0.1, 0.05, 0.15], &[0.0; 4], &config).unwrap();
+        let right = required_angle(&[0.0; 4], &[0.5, -0.1, 0.05, -0.15], &config).unwrap();
+
+        assert_eq!(left.lines, LaneLines::Left);
+        assert_eq!(right.lines, LaneLines::Right);
+        for single in [left, right] {
+            assert!(close(single.offset, both.unwrap().offset));
+            assert!(close(single.steering, both.unwrap().steering));
+        }
+        assert_eq!(required_angle(&[0.0; 4], &[0.0], &config), None);
+        assert_eq!(required_angle(&[], &[], &config), None);
+    }
 }
@@ -0,0 +1,342 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    adc::{Adc, AdcCalibration},
+    board,
+    drive::{Motors, Servo},
+    error::{Error, PyError},
+    layout,
+};
+
+// Li-ion open circuit voltage per cell -> charge percent
+const CELL_CURVE: [(f64, f64); 9] = [
+    (3.00, 0.0),
+    (3.30, 5.0),
+    (3.60, 20.0),
+    (3.70, 40.0),
+    (3.80, 60.0),
+    (3.90, 75.0),
+    (4.00, 85.0),
+    (4.10, 95.0),
+    (4.20, 100.0),
+];
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryState {
+    Unknown, // no sample yet
+    Ok,
+    Warning,
+    Cutoff, // latched until reset()
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryConfig {
+    #[pyo3(get, set)]
+    pub channel: u8, // A4 (robot-hat)
+    #[pyo3(get, set)]
+    pub divider: f64, // pack voltage / ADC pin voltage
+    #[pyo3(get, set)]
+    pub cells: u8,
+    #[pyo3(get, set)]
+    pub warning_voltage: f64,
+    #[pyo3(get, set)]
+    pub cutoff_voltage: f64,
+    #[pyo3(get, set)]
+    pub alpha: f64, // exponential filter weight of a new sample (0, 1]
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            channel: 4,
+            divider: 3.0,
+            cells: 2,
+            warning_voltage: 7.0,
+            cutoff_voltage: 6.6,
+            alpha: 0.2,
+        }
+    }
+}
+
+#[pymethods]
+impl BatteryConfig {
+    // Reads the channel named by the hardware layout (sensors.battery)
+    #[new]
+    pub fn new() -> Result<Self, PyError> {
+        Ok(BatteryConfig::from_layout()?)
+    }
+}
+
+impl BatteryConfig {
+    pub fn from_layout() -> Result<Self> {
+        let battery = layout::current().sensors.battery;
+        let channel = board::current()?
+            .pins
+            .analog(&battery)
+            .context("BATTERY CHANNEL")?;
+
+        Ok(Self {
+            channel: channel.index(),
+            ..Self::default()
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(self.alpha > 0.0 && self.alpha <= 1.0) {
+            bail!(Error::invalid(format!(
+                "BATTERY FILTER ALPHA {} OUT OF RANGE (0, 1]",
+                self.alpha
+            )));
+        }
+        if self.cells == 0 {
+            bail!(Error::invalid("BATTERY NEEDS AT LEAST ONE CELL"));
+        }
+        if self.cutoff_voltage >= self.warning_voltage {
+            bail!(Error::invalid(format!(
+                "BATTERY CUTOFF {} V MUST BE BELOW WARNING {} V",
+                self.cutoff_voltage, self.warning_voltage
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn cell_percentage(cell_voltage: f64) -> f64 {
+    let (first, last) = (CELL_CURVE[0], CELL_CURVE[CELL_CURVE.len() - 1]);
+    if cell_voltage <= first.0 {
+        return first.1;
+    }
+    if cell_voltage >= last.0 {
+        return last.1;
+    }
+
+    let upper = CELL_CURVE
+        .iter()
+        .position(|&(voltage, _)| voltage >= cell_voltage)
+        .unwrap_or(CELL_CURVE.len() - 1);
+    let (v0, p0) = CELL_CURVE[upper - 1];
+    let (v1, p1) = CELL_CURVE[upper];
+
+    p0 + (cell_voltage - v0) * (p1 - p0) / (v1 - v0)
+}
+
+#[pyclass]
+pub struct BatteryMonitor {
+    adc: Adc,
+    config: BatteryConfig,
+    voltage: Option<f64>, // filtered pack voltage
+    state: BatteryState,
+}
+
+impl BatteryMonitor {
+    pub fn with_adc(mut adc: Adc, config: BatteryConfig) -> Result<Self> {
+        config.validate()?;
+        adc.calibrate(config.channel, AdcCalibration::new(config.divider, 0.0))
+            .context("BATTERY ADC CHANNEL")?;
+
+        Ok(Self {
+            adc,
+            config,
+            voltage: None,
+            state: BatteryState::Unknown,
+        })
+    }
+
+    // Samples the pack and, at cutoff, stops the motors and centres the servos
+    pub fn guard(&mut self, motors: Option<&Motors>, servos: &[Servo]) -> Result<BatteryState> {
+        let state = self.update()?;
+        if state == BatteryState::Cutoff {
+            // park everything before reporting the first failure
+            let mut parked = Ok(());
+            if let Some(motors) = motors {
+                parked = parked.and(motors.emergency_stop());
+            }
+            for servo in servos {
+                parked = parked.and(servo.angle(0));
+            }
+            parked.context("BATTERY CUTOFF PARKING FAILED")?;
+        }
+
+        Ok(state)
+    }
+
+    fn classify(&self, voltage: f64) -> BatteryState {
+        if self.state == BatteryState::Cutoff || voltage <= self.config.cutoff_voltage {
+            BatteryState::Cutoff
+        } else if voltage <= self.config.warning_voltage {
+            BatteryState::Warning
+        } else {
+            BatteryState::Ok
+        }
+    }
+}
+
+#[pymethods]
+impl BatteryMonitor {
+    #[new]
+    // Default config on the layout's battery channel
+    #[pyo3(signature = (config = None))]
+    pub fn new(config: Option<BatteryConfig>) -> Result<Self, PyError> {
+        let config = match config {
+            Some(config) => config,
+            None => BatteryConfig::from_layout()?,
+        };
+        Ok(BatteryMonitor::with_adc(Adc::new()?, config)?)
+    }
+
+    #[getter]
+    pub fn config(&self) -> BatteryConfig {
+        self.config
+    }
+
+    #[setter]
+    pub fn set_config(&mut self, config: BatteryConfig) -> Result<(), PyError> {
+        config.validate()?;
+        self.adc
+            .calibrate(config.channel, AdcCalibration::new(config.divider, 0.0))?;
+        self.config = config;
+
+        Ok(())
+    }
+
+    // Takes one sample and returns the new state
+    pub fn update(&mut self) -> Result<BatteryState, PyError> {
+        let sample = self
+            .adc
+            .read_voltage(self.config.channel)
+            .context("BATTERY VOLTAGE READ FAILED")?;
+        let voltage = match self.voltage {
+            Some(voltage) => voltage + self.config.alpha * (sample - voltage),
+            None => sample,
+        };
+
+        self.voltage = Some(voltage);
+        self.state = self.classify(voltage);
+
+        Ok(self.state)
+    }
+
+    #[getter]
+    pub fn voltage(&self) -> Option<f64> {
+        self.voltage
+    }
+
+    #[getter]
+    pub fn percentage(&self) -> Option<f64> {
+        self.voltage
+            .map(|voltage| cell_percentage(voltage / self.config.cells as f64))
+    }
+
+    #[getter]
+    pub fn state(&self) -> BatteryState {
+        self.state
+    }
+
+    // Clears the filter and a latched cutoff (e.g. after swapping the pack)
+    pub fn reset(&mut self) {
+        self.voltage = None;
+        self.state = BatteryState::Unknown;
+    }
+
+    #[pyo3(name = "guard", signature = (motors = None, servos = vec![]))]
+    fn py_guard(
+        &mut self,
+        motors: Option<PyRef<'_, Motors>>,
+        servos: Vec<Servo>,
+    ) -> Result<BatteryState, PyError> {
+        Ok(self.guard(motors.as_deref(), &servos)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        drive::Motor,
+        hat::RobotHat,
+        mock::{MockBus, MockPin},
+    };
+
+    fn set_pack_voltage(mock: &MockBus, voltage: f64) {
+        let raw = (voltage / 3.0 / 3.3 * 4095.0).round() as u16;
+        mock.set_response(0x14, 0x13, &raw.to_be_bytes()); // A4
+    }
+
+    #[test]
+    fn percentage_interpolates_cell_curve() {
+        assert_eq!(cell_percentage(2.5), 0.0);
+        assert_eq!(cell_percentage(3.7), 40.0);
+        assert!((cell_percentage(3.75) - 50.0).abs() < 1e-9);
+        assert_eq!(cell_percentage(4.3), 100.0);
+    }
+
+    #[test]
+    fn filtered_voltage_walks_through_thresholds_and_latches_cutoff() {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let mut monitor = BatteryMonitor::with_adc(hat.adc(), BatteryConfig::default()).unwrap();
+
+        set_pack_voltage(&mock, 8.0);
+        assert_eq!(monitor.update().unwrap(), BatteryState::Ok);
+        assert!((monitor.percentage().unwrap() - 85.0).abs() < 0.1);
+
+        set_pack_voltage(&mock, 6.0);
+        assert_eq!(monitor.update().unwrap(), BatteryState::Ok); // 7.6 V filtered
+        while monitor.update().unwrap() == BatteryState::Ok {}
+        assert_eq!(monitor.state(), BatteryState::Warning);
+        while monitor.update().unwrap() == BatteryState::Warning {}
+        assert_eq!(monitor.state(), BatteryState::Cutoff);
+
+        set_pack_voltage(&mock, 8.0);
+        assert_eq!(monitor.update().unwrap(), BatteryState::Cutoff);
+        monitor.reset();
+        assert_eq!(monitor.update().unwrap(), BatteryState::Ok);
+    }
+
+    #[test]
+    fn guard_parks_actuators_at_cutoff() {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let left = Motor::with_parts(hat.pwm(12).unwrap(), Box::new(MockPin::new()), 50.0);
+        let right = Motor::with_parts(hat.pwm(13).unwrap(), Box::new(MockPin::new()), 50.0);
+        let motors = Motors::with_motors(left.unwrap(), right.unwrap());
+        let servos = [Servo::with_pwm(hat.pwm(2).unwrap(), 50.0).unwrap()];
+        let mut monitor = BatteryMonitor::with_adc(hat.adc(), BatteryConfig::default()).unwrap();
+
+        motors.forward(50).unwrap();
+        set_pack_voltage(&mock, 6.0);
+        mock.clear();
+        let state = monitor.guard(Some(&motors), &servos).unwrap();
+
+        assert_eq!(state, BatteryState::Cutoff);
+        let writes = mock.word_writes();
+        let last = |register| {
+            writes
+                .iter()
+                .rev()
+                .find(|w| w.register == register)
+                .unwrap()
+        };
+        assert_eq!(last(0x2C).mcu_value(), 0);
+        assert_eq!(last(0x2D).mcu_value(), 0);
+        assert_eq!(last(0x22).mcu_value(), 4500);
+    }
+
+    #[test]
+    fn invalid_thresholds_are_rejected() {
+        let hat = RobotHat::with_bus(Box::new(MockBus::new())).unwrap();
+        let config = BatteryConfig {
+            cutoff_voltage: 7.5,
+            ..BatteryConfig::default()
+        };
+
+        assert!(BatteryMonitor::with_adc(hat.adc(), config).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{str::FromStr, sync::Mutex, thread::sleep, time::Duration};
+
+use anyhow::{Context, Result};
+use rppal::gpio::{Gpio, Level};
+
+use crate::{
+    error::{Error, PyError},
+    hal::{DigitalInput, DigitalOutput},
+    pins::{PinMap, PINS_V1, PINS_V2},
+};
+
+// Strap pin read at start up, pulled low on the newer HAT revision (ref: robot-hat Pin.check_board_type)
+pub const BOARD_TYPE_PIN: u8 = 12;
+
+static BOARD: Mutex<Option<BoardProfile>> = Mutex::new(None);
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peripheral {
+    Pwm,
+    Adc,
+    Motors,
+    Ultrasonic,
+    Ble,
+}
+
+const PERIPHERALS: [Peripheral; 5] = [
+    Peripheral::Pwm,
+    Peripheral::Adc,
+    Peripheral::Motors,
+    Peripheral::Ultrasonic,
+    Peripheral::Ble,
+];
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardRevision {
+    V1, // BOARD_TYPE high, MCU reset on BCM 5
+    V2, // BOARD_TYPE low, MCU reset on BCM 21
+}
+
+impl FromStr for BoardRevision {
+    type Err = Error;
+
+    fn from_str(revision: &str) -> Result<Self, Error> {
+        match revision {
+            "v1" => Ok(BoardRevision::V1),
+            "v2" => Ok(BoardRevision::V2),
+            _ => Err(Error::invalid(format!(
+                "UNKNOWN BOARD REVISION {revision:?} (expected v1 or v2)"
+            ))),
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardProfile {
+    #[pyo3(get)]
+    pub revision: BoardRevision,
+    #[pyo3(get)]
+    pub pins: PinMap,
+    peripherals: &'static [Peripheral],
+}
+
+impl BoardProfile {
+    pub fn for_revision(revision: BoardRevision) -> Self {
+        match revision {
+            BoardRevision::V1 => Self {
+                revision,
+                pins: PinMap::new(&PINS_V1),
+                peripherals: &PERIPHERALS,
+            },
+            BoardRevision::V2 => Self {
+                revision,
+                pins: PinMap::new(&PINS_V2),
+                peripherals: &PERIPHERALS,
+            },
+        }
+    }
+
+    pub fn detect_with(board_type: &dyn DigitalInput) -> Self {
+        match board_type.read() {
+            Level::Low => BoardProfile::for_revision(BoardRevision::V2),
+            Level::High => BoardProfile::for_revision(BoardRevision::V1),
+        }
+    }
+
+    // Pulses the MCU reset line low for 1 ms
+    pub fn reset_with(rst: &mut dyn DigitalOutput) {
+        rst.write(Level::Low);
+        sleep(Duration::from_millis(1));
+        rst.write(Level::High);
+        sleep(Duration::from_millis(1));
+    }
+}
+
+#[pymethods]
+impl BoardProfile {
+    #[new]
+    pub fn new(revision: BoardRevision) -> Self {
+        BoardProfile::for_revision(revision)
+    }
+
+    // Reads the BOARD_TYPE strap
+    #[staticmethod]
+    pub fn detect() -> Result<Self, PyError> {
+        let gpio = Gpio::new()
+            .map_err(Error::from)
+            .context("Gpio init failed (board)")?;
+        let board_type = gpio
+            .get(BOARD_TYPE_PIN)
+            .map_err(Error::from)
+            .context("BOARD TYPE PIN INIT FAILED")?
+            .into_input();
+
+        Ok(BoardProfile::detect_with(&board_type))
+    }
+
+    #[getter]
+    pub fn reset_pin(&self) -> u8 {
+        self.pin("MCURST").unwrap_or_default()
+    }
+
+    // BCM number of a digital pin, e.g. "D4"
+    pub fn pin(&self, name: &str) -> Result<u8, PyError> {
+        Ok(self.pins.gpio(name)?)
+    }
+
+    #[getter]
+    pub fn peripherals(&self) -> Vec<Peripheral> {
+        self.peripherals.to_vec()
+    }
+
+    pub fn has(&self, peripheral: Peripheral) -> bool {
+        self.peripherals.contains(&peripheral)
+    }
+
+    pub fn reset_mcu(&self) -> Result<(), PyError> {
+        let mut rst = Gpio::new()
+            .map_err(Error::from)
+            .context("Gpio init failed (board)")?
+            .get(self.reset_pin())
+            .map_err(Error::from)
+            .context("MCU RESET PIN INIT FAILED")?
+            .into_output();
+        BoardProfile::reset_with(&mut rst);
+
+        Ok(())
+    }
+}
+
+// Process wide profile: the override if one was set, otherwise detected on first use
+pub fn current() -> Result<BoardProfile> {
+    let mut board = BOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(profile) = *board {
+        return Ok(profile);
+    }
+
+    let profile = BoardProfile::detect().context("BOARD DETECTION FAILED")?;
+    *board = Some(profile);
+
+    Ok(profile)
+}
+
+// Pins the profile for the rest of the process, `None` goes back to detection
+pub fn set_current(profile: Option<BoardProfile>) {
+    *BOARD.lock().unwrap_or_else(|e| e.into_inner()) = profile;
+}
+
+// Override from Python ("v1" / "v2"), or the current profile
+pub fn select(revision: Option<&str>) -> Result<BoardProfile> {
+    match revision {
+        Some(revision) => {
+            let profile = BoardProfile::for_revision(revision.parse()?);
+            set_current(Some(profile));
+            Ok(profile)
+        }
+        None => current(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPin;
+
+    #[test]
+    fn board_type_strap_selects_reset_pin() {
+        let v1 = BoardProfile::detect_with(&MockPin::with_level(Level::High));
+        let v2 = BoardProfile::detect_with(&MockPin::with_level(Level::Low));
+
+        assert_eq!(v1.revision, BoardRevision::V1);
+        assert_eq!(v1.reset_pin(), 5);
+        assert_eq!(v2.revision, BoardRevision::V2);
+        assert_eq!(v2.reset_pin(), 21);
+    }
+
+    #[test]
+    fn profiles_expose_pins_and_peripherals() {
+        let v2 = BoardProfile::for_revision(BoardRevision::V2);
+
+        assert_eq!(v2.pin("D4").unwrap(), 23);
+        assert_eq!(v2.pin("D5").unwrap(), 24);
+        assert!(v2.pin("D42").is_err());
+        assert!(v2.has(Peripheral::Motors));
+        assert!(v2.peripherals().contains(&Peripheral::Adc));
+    }
+
+    #[test]
+    fn reset_pulses_low_then_high() {
+        let mut rst = MockPin::new();
+        BoardProfile::reset_with(&mut rst);
+
+        assert_eq!(rst.levels(), vec![Level::Low, Level::High]);
+    }
+
+    #[test]
+    fn override_replaces_detection() {
+        let profile = select(Some("v2")).unwrap();
+        assert_eq!(profile.reset_pin(), 21);
+        assert_eq!(current().unwrap(), profile);
+        assert!(select(Some("v3")).is_err());
+
+        set_current(None);
+    }
+}
@@ -0,0 +1,340 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::{
+    axel::{eval, slope},
+    error::PyError,
+    timing::seconds,
+    vehicle::VehicleGeometry,
+};
+
+// Steering laws for following a path y = p(x) in the road frame of the camera
+// (x ahead, y to the left, metres, highest power first), e.g. axel::centre_line()
+pub trait SteeringController: Send {
+    // Front wheel angle (deg, positive to the right) within geometry.max_steering.
+    // `speed` in m/s, `dt` since the previous call
+    fn steer(&mut self, path: &[f64], speed: f64, dt: Duration) -> f64;
+
+    // Forgets accumulated state, e.g. after the lane was lost
+    fn reset(&mut self) {}
+}
+
+// On the lateral offset of the path `preview` metres ahead (positive when it is to the right)
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidController {
+    #[pyo3(get, set)]
+    pub kp: f64, // deg per m
+    #[pyo3(get, set)]
+    pub ki: f64, // deg per m s
+    #[pyo3(get, set)]
+    pub kd: f64, // deg s per m
+    #[pyo3(get, set)]
+    pub preview: f64, // m
+    #[pyo3(get, set)]
+    pub derivative_filter: f64, // low-pass time constant on the derivative (s), 0 for none
+    #[pyo3(get, set)]
+    pub geometry: VehicleGeometry,
+    integral: f64,
+    previous: Option<f64>,
+    derivative: f64,
+}
+
+impl SteeringController for PidController {
+    fn steer(&mut self, path: &[f64], _speed: f64, dt: Duration) -> f64 {
+        let error = -eval(path, self.preview);
+        let dt = dt.as_secs_f64();
+        if dt > 0.0 {
+            let raw = self
+                .previous
+                .map_or(0.0, |previous| (error - previous) / dt);
+            self.derivative += (raw - self.derivative) * dt / (self.derivative_filter + dt);
+        }
+        self.previous = Some(error);
+
+        // anti-windup: stop integrating while the output is pinned in the error's direction
+        let limit = self.geometry.max_steering;
+        let proportional = self.kp * error + self.kd * self.derivative;
+        let integral = self.integral + error * dt;
+        let output = proportional + self.ki * integral;
+        if output.abs() <= limit || output.signum() != error.signum() {
+            self.integral = integral;
+        }
+
+        (proportional + self.ki * self.integral).clamp(-limit, limit)
+    }
+
+    fn reset(&mut self) {
+        self.integral = 0.0;
+        self.previous = None;
+        self.derivative = 0.0;
+    }
+}
+
+#[pymethods]
+impl PidController {
+    #[new]
+    #[pyo3(signature = (
+        kp = 100.0,
+        ki = 10.0,
+        kd = 5.0,
+        preview = 0.1,
+        derivative_filter = 0.05,
+        geometry = None
+    ))]
+    pub fn new(
+        kp: f64,
+        ki: f64,
+        kd: f64,
+        preview: f64,
+        derivative_filter: f64,
+        geometry: Option<VehicleGeometry>,
+    ) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            preview,
+            derivative_filter,
+            geometry: geometry.unwrap_or_default(),
+            integral: 0.0,
+            previous: None,
+            derivative: 0.0,
+        }
+    }
+
+    #[pyo3(name = "steer")]
+    fn py_steer(&mut self, path: Vec<f64>, speed: f64, dt_s: f64) -> Result<f64, PyError> {
+        Ok(self.steer(&path, speed, seconds("CONTROLLER DT", dt_s)?))
+    }
+
+    #[pyo3(name = "reset")]
+    fn py_reset(&mut self) {
+        self.reset();
+    }
+
+    // Accumulated error (m s)
+    #[getter]
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+}
+
+// Arc through the path point `lookahead + speed_gain * speed` metres away
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PurePursuit {
+    #[pyo3(get, set)]
+    pub lookahead: f64, // m
+    #[pyo3(get, set)]
+    pub speed_gain: f64, // s, looks further ahead when faster
+    #[pyo3(get, set)]
+    pub geometry: VehicleGeometry,
+}
+
+impl PurePursuit {
+    // Point of the path `distance` from the camera, None when the path never gets that far
+    pub fn target(path: &[f64], distance: f64) -> Option<(f64, f64)> {
+        let gap = |x: f64| x * x + eval(path, x).powi(2) - distance * distance;
+        if gap(0.0) > 0.0 {
+            return None;
+        }
+
+        // gap(distance) >= 0, bisect for the crossing
+        let (mut near, mut far) = (0.0, distance);
+        for _ in 0..50 {
+            let mid = (near + far) / 2.0;
+            if gap(mid) > 0.0 {
+                far = mid;
+            } else {
+                near = mid;
+            }
+        }
+
+        Some((near, eval(path, near)))
+    }
+}
+
+impl SteeringController for PurePursuit {
+    fn steer(&mut self, path: &[f64], speed: f64, _dt: Duration) -> f64 {
+        let distance = self.lookahead + self.speed_gain * speed.abs();
+        // beside the car already: aim square on
+        let (x, y) = PurePursuit::target(path, distance).unwrap_or((0.0, eval(path, 0.0)));
+        let curvature = -2.0 * y / (x * x + y * y); // positive to the right
+        self.geometry.steering_angle(curvature)
+    }
+}
+
+#[pymethods]
+impl PurePursuit {
+    #[new]
+    #[pyo3(signature = (lookahead = 0.25, speed_gain = 0.5, geometry = None))]
+    pub fn new(lookahead: f64, speed_gain: f64, geometry: Option<VehicleGeometry>) -> Self {
+        Self {
+            lookahead,
+            speed_gain,
+            geometry: geometry.unwrap_or_default(),
+        }
+    }
+
+    #[pyo3(name = "steer")]
+    fn py_steer(&mut self, path: Vec<f64>, speed: f64, dt_s: f64) -> Result<f64, PyError> {
+        Ok(self.steer(&path, speed, seconds("CONTROLLER DT", dt_s)?))
+    }
+
+    // Stateless, kept so every controller can be swapped for another
+    #[pyo3(name = "reset")]
+    fn py_reset(&mut self) {
+        self.reset();
+    }
+}
+
+// Heading error plus atan(gain * offset / (softening + speed)), both at the camera
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stanley {
+    #[pyo3(get, set)]
+    pub gain: f64, // 1/s
+    #[pyo3(get, set)]
+    pub softening: f64, // m/s, keeps the offset term sane at low speed
+    #[pyo3(get, set)]
+    pub geometry: VehicleGeometry,
+}
+
+impl SteeringController for Stanley {
+    fn steer(&mut self, path: &[f64], speed: f64, _dt: Duration) -> f64 {
+        let heading_error = -slope(path, 0.0).atan();
+        let offset = -eval(path, 0.0);
+        let correction = (self.gain * offset / (self.softening + speed.abs())).atan();
+        let limit = self.geometry.max_steering;
+
+        (heading_error + correction)
+            .to_degrees()
+            .clamp(-limit, limit)
+    }
+}
+
+#[pymethods]
+impl Stanley {
+    #[new]
+    #[pyo3(signature = (gain = 2.5, softening = 0.1, geometry = None))]
+    pub fn new(gain: f64, softening: f64, geometry: Option<VehicleGeometry>) -> Self {
+        Self {
+            gain,
+            softening,
+            geometry: geometry.unwrap_or_default(),
+        }
+    }
+
+    #[pyo3(name = "steer")]
+    fn py_steer(&mut self, path: Vec<f64>, speed: f64, dt_s: f64) -> Result<f64, PyError> {
+        Ok(self.steer(&path, speed, seconds("CONTROLLER DT", dt_s)?))
+    }
+
+    // Nothing to forget either
+    #[pyo3(name = "reset")]
+    fn py_reset(&mut self) {
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: Duration = Duration::from_millis(50);
+
+    fn controllers() -> Vec<Box<dyn SteeringController>> {
+        vec![
+            Box::new(PidController::new(100.0, 10.0, 5.0, 0.1, 0.05, None)),
+            Box::new(PurePursuit::new(0.25, 0.5, None)),
+            Box::new(Stanley::new(2.5, 0.1, None)),
+        ]
+    }
+
+    #[test]
+    fn all_steer_towards_the_path_within_limits() {
+        for mut controller in controllers() {
+            assert_eq!(controller.steer(&[0.0], 0.3, DT), 0.0);
+            controller.reset();
+            assert!(controller.steer(&[-0.05], 0.3, DT) > 0.0); // path to the right
+            controller.reset();
+            assert!(controller.steer(&[0.0, 0.0, 0.4, 0.0], 0.3, DT) < 0.0); // bending left
+            controller.reset();
+
+            let far = controller.steer(&[-5.0], 0.3, DT);
+            assert!(far > 0.0 && far <= 30.0, "{far}");
+            let sharp = controller.steer(&[0.0, 5.0, 1.0, 0.0], 0.3, DT);
+            assert!((-30.0..0.0).contains(&sharp), "{sharp}");
+        }
+    }
+
+    #[test]
+    fn pid_integral_stops_winding_up_at_the_limit() {
+        let mut pid = PidController::new(100.0, 50.0, 0.0, 0.0, 0.0, None);
+
+        // 10 deg proportional plus 5 deg more per second of integral
+        for expected in [15.0, 20.0, 25.0] {
+            assert!((pid.steer(&[-0.1], 0.0, Duration::from_secs(1)) - expected).abs() < 1e-9);
+        }
+        for _ in 0..20 {
+            assert_eq!(pid.steer(&[-0.1], 0.0, Duration::from_secs(1)), 30.0);
+        }
+        assert!(pid.integral() <= 0.4 + 1e-9);
+        // recovers as soon as the error flips instead of unwinding 20 s of integral
+        assert!(pid.steer(&[0.1], 0.0, Duration::from_secs(1)) < 30.0);
+
+        pid.reset();
+        assert_eq!(pid.integral(), 0.0);
+    }
+
+    #[test]
+    fn pid_derivative_is_filtered() {
+        let mut raw = PidController::new(0.0, 0.0, 1.0, 0.0, 0.0, None);
+        let mut filtered = PidController::new(0.0, 0.0, 1.0, 0.0, 0.2, None);
+        for pid in [&mut raw, &mut filtered] {
+            pid.steer(&[0.0], 0.0, DT);
+        }
+
+        // a 0.01 m step in one 50 ms frame: 0.2 m/s raw, a fifth of it through the filter
+        let step = [-0.01];
+        assert!((raw.steer(&step, 0.0, DT) - 0.2).abs() < 1e-9);
+        assert!((filtered.steer(&step, 0.0, DT) - 0.04).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pure_pursuit_aims_at_the_lookahead_circle() {
+        let straight_right = [-0.1];
+        let (x, y) = PurePursuit::target(&straight_right, 0.25).unwrap();
+        assert!((x * x + y * y - 0.0625).abs() < 1e-9);
+        assert_eq!(PurePursuit::target(&[-0.5], 0.25), None);
+
+        // further ahead when faster, so gentler
+        let mut pursuit = PurePursuit::new(0.25, 0.5, None);
+        let slow = pursuit.steer(&straight_right, 0.0, DT);
+        let fast = pursuit.steer(&straight_right, 1.0, DT);
+        assert!(slow > fast && fast > 0.0);
+        let expected = (0.095 * 2.0 * 0.1 / 0.0625f64).atan().to_degrees();
+        assert!((slow - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stanley_trades_offset_against_speed() {
+        let mut stanley = Stanley::new(2.5, 0.1, None);
+        let offset = [-0.02];
+
+        let slow = stanley.steer(&offset, 0.1, DT);
+        let fast = stanley.steer(&offset, 1.0, DT);
+        assert!((slow - (2.5 * 0.02 / 0.2f64).atan().to_degrees()).abs() < 1e-9);
+        assert!(slow > fast);
+
+        // heading only: the lane turns 10 deg to the right
+        let turning = [-(10f64.to_radians().tan()), 0.0];
+        assert!((stanley.steer(&turning, 0.5, DT) - 10.0).abs() < 1e-9);
+    }
+}
@@ -2,129 +2,1584 @@
 
 use pyo3::prelude::*;
 
-use anyhow::{Context, Result};
-use rppal::gpio::{Gpio, Level, OutputPin};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    sync::{Arc, Condvar, Mutex, MutexGuard, Weak},
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
 
-use crate::{map_range, PWM};
+use anyhow::{bail, Context, Result};
+use rppal::gpio::{Gpio, Level};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board,
+    error::{Error, PyError},
+    hal::DigitalOutput,
+    hat::RobotHat,
+    layout, map_range,
+    odometry::Odometry,
+    protocol::{Channel, Command},
+    shutdown::ParkGuard,
+    timing::{seconds, PwmTiming},
+    watchdog::{Watchdog, WatchdogStatus},
+    PWM,
+};
 
 // Servo and Motor Constants
-const PERIOD: u16 = 1200;
-const PRESCALER: u16 = 1200;
-// const FREQ: u16 = 50;
+pub const MOTOR_FREQ: f64 = 50.0;
+pub const SERVO_FREQ: f64 = 50.0;
 const MAX_PW: u16 = 2500;
 const MIN_PW: u16 = 500;
-// const CLOCK: u32 = 72_000_000;
+
+const RAMP_TICK: Duration = Duration::from_millis(20);
+const SERVO_TICK: Duration = Duration::from_millis(20);
+
+// Read by motors_init() / servos_init() when no path is given
+pub const MOTOR_CALIBRATION_PATH: &str = "motors.toml";
+pub const SERVO_CALIBRATION_PATH: &str = "servos.toml";
+
+// Maps a requested speed (-100..100) to the duty actually sent to one motor
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MotorCalibration {
+    #[pyo3(get, set)]
+    pub inverted: bool, // motor wired (or mounted) backwards
+    #[pyo3(get, set)]
+    pub trim: f64, // gain, e.g. 0.95 on the faster side to stop straight-line drift
+    #[pyo3(get, set)]
+    pub min_duty: u32, // lowest duty (%) that turns the wheel, speed 1 starts here
+}
+
+impl Default for MotorCalibration {
+    fn default() -> Self {
+        Self {
+            inverted: false,
+            trim: 1.0,
+            min_duty: 0,
+        }
+    }
+}
+
+#[pymethods]
+impl MotorCalibration {
+    #[new]
+    #[pyo3(signature = (inverted = false, trim = 1.0, min_duty = 0))]
+    pub fn new(inverted: bool, trim: f64, min_duty: u32) -> Self {
+        Self {
+            inverted,
+            trim,
+            min_duty,
+        }
+    }
+
+    // Signed duty (%) for `speed`: trimmed, then 1..100 remapped onto min_duty..100
+    pub fn duty(&self, speed: i32) -> i32 {
+        let speed = speed.clamp(-100, 100);
+        if speed == 0 {
+            return 0;
+        }
+
+        let trimmed = (speed.unsigned_abs() as f64 * self.trim).min(100.0);
+        let min_duty = self.min_duty.min(100) as f64;
+        let duty = (min_duty + trimmed * (100.0 - min_duty) / 100.0).round() as i32;
+        let duty = if self.inverted { -duty } else { duty };
+
+        duty * speed.signum()
+    }
+}
+
+impl MotorCalibration {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.trim.is_finite() && self.trim > 0.0) {
+            bail!(Error::Calibration(format!(
+                "MOTOR TRIM {} MUST BE A POSITIVE NUMBER",
+                self.trim
+            )));
+        }
+        if self.min_duty >= 100 {
+            bail!(Error::Calibration(format!(
+                "MOTOR MIN DUTY {} MUST BE BELOW 100",
+                self.min_duty
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// Both drive motors. The right one is inverted by default, as in the robot-hat python module
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MotorsCalibration {
+    #[pyo3(get, set)]
+    pub left: MotorCalibration,
+    #[pyo3(get, set)]
+    pub right: MotorCalibration,
+}
+
+impl Default for MotorsCalibration {
+    fn default() -> Self {
+        Self {
+            left: MotorCalibration::default(),
+            right: MotorCalibration {
+                inverted: true,
+                ..MotorCalibration::default()
+            },
+        }
+    }
+}
+
+#[pymethods]
+impl MotorsCalibration {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[staticmethod]
+    pub fn load(path: &str) -> Result<Self, PyError> {
+        Ok(read_motor_calibration(Path::new(path))?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), PyError> {
+        let text = toml::to_string(self).context("SERIALISING MOTOR CALIBRATION")?;
+        fs::write(path, text)
+            .map_err(Error::from)
+            .with_context(|| format!("WRITING MOTOR CALIBRATION {path}"))?;
+
+        Ok(())
+    }
+}
+
+impl MotorsCalibration {
+    // `path` if it exists, otherwise the defaults
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        read_motor_calibration(path)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.left.validate().context("LEFT MOTOR")?;
+        self.right.validate().context("RIGHT MOTOR")
+    }
+}
+
+fn read_motor_calibration(path: &Path) -> Result<MotorsCalibration> {
+    let text = fs::read_to_string(path)
+        .map_err(Error::from)
+        .with_context(|| format!("READING MOTOR CALIBRATION {}", path.display()))?;
+    let calibration: MotorsCalibration = toml::from_str(&text)
+        .map_err(Error::from)
+        .with_context(|| format!("PARSING MOTOR CALIBRATION {}", path.display()))?;
+    calibration
+        .validate()
+        .with_context(|| format!("INVALID MOTOR CALIBRATION {}", path.display()))?;
+
+    Ok(calibration)
+}
 
 #[pyclass]
 pub struct Motor {
     pub pwm: PWM,
-    pub dir: OutputPin,
+    pub dir: Box<dyn DigitalOutput>,
+    pub calibration: MotorCalibration,
+    _park: ParkGuard, // zero duty on shutdown, until dropped
+}
+
+impl Motor {
+    pub fn with_parts(mut pwm: PWM, dir: Box<dyn DigitalOutput>, freq: f64) -> Result<Self> {
+        pwm.freq(freq).context("MOTOR FREQ INIT FAILED")?;
+        let park = ParkGuard::register(pwm.hat.clone(), Command::pulse_width(pwm.channel, 0));
+
+        Ok(Self {
+            pwm,
+            dir,
+            calibration: MotorCalibration::default(),
+            _park: park,
+        })
+    }
+
+    pub fn with_hat(hat: &RobotHat, pwm_pin: u8, dir_pin: u8, freq: f64) -> Result<Self> {
+        let gpio = Gpio::new()
+            .map_err(Error::from)
+            .context("Gpio init failed (drive)")?;
+
+        let pwm = hat.pwm(pwm_pin).context("PWM init failed")?;
+        let dir = gpio
+            .get(dir_pin)
+            .map_err(Error::from)
+            .context("Gpio init failed")?
+            .into_output();
+
+        Motor::with_parts(pwm, Box::new(dir), freq)
+    }
 }
 
 #[pymethods]
 impl Motor {
     #[new]
-    pub fn new(pwm_pin: u8, dir_pin: u8) -> Result<Self> {
-        let gpio = Gpio::new().context("Gpio init failed (drive)")?;
+    #[pyo3(signature = (pwm_pin, dir_pin, freq = MOTOR_FREQ))]
+    pub fn new(pwm_pin: u8, dir_pin: u8, freq: f64) -> Result<Self, PyError> {
+        let hat = RobotHat::shared().context("PWM I2C INIT FAILED")?;
+        Ok(Motor::with_hat(&hat, pwm_pin, dir_pin, freq)?)
+    }
+
+    pub fn speed(&mut self, speed: i32) -> Result<(), PyError> {
+        let duty = self.calibration.duty(speed);
+        let dir: Level = if duty > 0 { Level::High } else { Level::Low };
 
-        let mut pwm = PWM::new(pwm_pin).context("PWM init failed")?;
-        let dir = gpio.get(dir_pin).context("Gpio init failed")?.into_output();
+        self.pwm.pulse_width_percent(duty.unsigned_abs())?;
+        self.dir.write(dir);
 
-        pwm.period(PERIOD)?;
-        pwm.prescaler(PRESCALER)?;
-        Ok(Self { pwm, dir })
+        Ok(())
     }
 
-    pub fn speed(&mut self, speed: i32) -> Result<()> {
-        let dir: Level = if speed > 0 { Level::High } else { Level::Low };
+    pub fn stop(&mut self) -> Result<(), PyError> {
+        Ok(self.pwm.pulse_width(0).context("MOTOR STOP FAILED")?)
+    }
 
-        self.pwm.pulse_width_percent(speed.unsigned_abs())?;
-        self.dir.write(dir);
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> Result<bool, PyError> {
+        self.stop()?;
+        Ok(false)
+    }
+
+    #[getter]
+    pub fn calibration(&self) -> MotorCalibration {
+        self.calibration
+    }
+
+    #[setter]
+    pub fn set_calibration(&mut self, calibration: MotorCalibration) -> Result<(), PyError> {
+        calibration.validate()?;
+        self.calibration = calibration;
 
         Ok(())
     }
 }
 
+// Slew-rate limit on the commanded speed of each motor
 #[pyclass]
-pub struct Motors {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampConfig {
+    #[pyo3(get, set)]
+    pub rate: f64, // max change in duty (%) per second, 0 disables the ramp
+    #[pyo3(get, set)]
+    pub background: bool, // ramp on a thread, otherwise only when tick() is called
+}
+
+impl Default for RampConfig {
+    fn default() -> Self {
+        Self {
+            rate: 0.0,
+            background: true,
+        }
+    }
+}
+
+#[pymethods]
+impl RampConfig {
+    #[new]
+    #[pyo3(signature = (rate = 0.0, background = true))]
+    pub fn new(rate: f64, background: bool) -> Self {
+        Self { rate, background }
+    }
+}
+
+impl RampConfig {
+    fn validate(&self) -> Result<()> {
+        if !(self.rate.is_finite() && self.rate >= 0.0) {
+            bail!(Error::invalid(format!(
+                "MOTOR RAMP RATE {} MUST BE 0 OR POSITIVE",
+                self.rate
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// Both motors and the ramp between the commanded (target) and sent (output) speeds
+pub struct MotorPair {
     pub left_motor: Motor,
     pub right_motor: Motor,
+    pub ramp: RampConfig,
+    target: [f64; 2],
+    output: [f64; 2],
+    last_tick: Option<Instant>,
+    ticking: bool,                // background ramp thread running
+    watchdog: Option<Watchdog>,   // fed by every command
+    fault: Option<anyhow::Error>, // last error of the ramp thread, returned by the next command
+    odometry: Option<Odometry>,   // told the mean output on every write
+}
+
+impl MotorPair {
+    pub fn target(&self) -> (f64, f64) {
+        (self.target[0], self.target[1])
+    }
+
+    pub fn output(&self) -> (f64, f64) {
+        (self.output[0], self.output[1])
+    }
+
+    pub fn settled(&self) -> bool {
+        self.output == self.target
+    }
+
+    // Moves the outputs towards the targets by at most `rate * dt` and sends them
+    pub fn advance(&mut self, dt: Duration) -> Result<bool> {
+        let step = self.ramp.rate * dt.as_secs_f64();
+        for (output, target) in self.output.iter_mut().zip(self.target) {
+            if self.ramp.rate <= 0.0 || (target - *output).abs() <= step {
+                *output = target;
+            } else {
+                *output += step.copysign(target - *output);
+            }
+        }
+        self.write()?;
+
+        Ok(self.settled())
+    }
+
+    // Advances by the time since the last tick, returns true once both targets are reached
+    pub fn tick(&mut self) -> Result<bool> {
+        let now = Instant::now();
+        let dt = now - self.last_tick.unwrap_or(now);
+        self.last_tick = Some(now);
+
+        self.advance(dt)
+    }
+
+    fn write(&mut self) -> Result<()> {
+        // try both sides before reporting, a stuck left motor must not keep the right one running
+        let [left, right] = self.output.map(|speed| speed.round() as i32);
+        let left = self
+            .left_motor
+            .speed(left)
+            .context("LEFT MOTOR SPEED FAILED");
+        let right = self
+            .right_motor
+            .speed(right)
+            .context("RIGHT MOTOR SPEED FAILED");
+        if let Some(odometry) = &self.odometry {
+            odometry
+                .lock()
+                .set_speed((self.output[0] + self.output[1]) / 2.0);
+        }
+
+        left.and(right)
+    }
+}
+
+impl Drop for Motor {
+    fn drop(&mut self) {
+        let _ = self.pwm.pulse_width(0);
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct Motors {
+    inner: Arc<Mutex<MotorPair>>,
 }
 
-#[pymethods]
 impl Motors {
-    #[new]
-    pub fn new() -> Result<Self> {
-        let left_motor_pwm_pin: u8 = 12; // P12 (robot-hat)
-        let left_motor_dir_pin: u8 = 23; // D4 (robot-hat)
-        let right_motor_pwm_pin: u8 = 13; // P13 (robot-hat)
-        let right_motor_dir_pin: u8 = 24; // D5 (robot-hat)
-
-        let left_motor =
-            Motor::new(left_motor_pwm_pin, left_motor_dir_pin).context("LEFT MOTOR INIT FAILED")?;
-        let right_motor = Motor::new(right_motor_pwm_pin, right_motor_dir_pin)
-            .context("RIGHT MOTOR INIT FAILED")?;
+    // Installs the default calibration, i.e. the right motor is inverted
+    pub fn with_motors(mut left_motor: Motor, mut right_motor: Motor) -> Self {
+        let calibration = MotorsCalibration::default();
+        left_motor.calibration = calibration.left;
+        right_motor.calibration = calibration.right;
 
-        Ok(Self {
+        let pair = MotorPair {
             left_motor,
             right_motor,
-        })
+            ramp: RampConfig::default(),
+            target: [0.0; 2],
+            output: [0.0; 2],
+            last_tick: None,
+            ticking: false,
+            watchdog: None,
+            fault: None,
+            odometry: None,
+        };
+
+        Self {
+            inner: Arc::new(Mutex::new(pair)),
+        }
+    }
+
+    pub fn with_hat(hat: &RobotHat, freq: f64) -> Result<Self> {
+        let pins = board::current()?.pins;
+        let layout = layout::current().motors;
+        let left_motor_pwm_pin = pins.pwm(&layout.left_pwm)?.index();
+        let left_motor_dir_pin = pins.gpio(&layout.left_dir)?;
+        let right_motor_pwm_pin = pins.pwm(&layout.right_pwm)?.index();
+        let right_motor_dir_pin = pins.gpio(&layout.right_dir)?;
+
+        let left_motor = Motor::with_hat(hat, left_motor_pwm_pin, left_motor_dir_pin, freq)
+            .context("LEFT MOTOR INIT FAILED")?;
+        let right_motor = Motor::with_hat(hat, right_motor_pwm_pin, right_motor_dir_pin, freq)
+            .context("RIGHT MOTOR INIT FAILED")?;
+
+        Ok(Motors::with_motors(left_motor, right_motor))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, MotorPair> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Sets new targets, sent straight away unless a ramp is configured
+    pub fn command(&self, left: f64, right: f64) -> Result<()> {
+        let mut pair = self.lock();
+        if let Some(watchdog) = &pair.watchdog {
+            watchdog.feed();
+        }
+        if let Some(fault) = pair.fault.take() {
+            return Err(fault.context("MOTOR RAMP FAILED"));
+        }
+
+        if pair.settled() {
+            pair.last_tick = Some(Instant::now());
+        }
+        pair.target = [left.clamp(-100.0, 100.0), right.clamp(-100.0, 100.0)];
+        if pair.ramp.rate <= 0.0 {
+            return pair.advance(Duration::ZERO).map(drop);
+        }
+
+        if pair.ramp.background && !pair.ticking && !pair.settled() {
+            pair.ticking = true;
+            spawn_ramp(Arc::downgrade(&self.inner));
+        }
+
+        Ok(())
     }
 
-    pub fn stop(&mut self) {
-        let _ = self.left_motor.speed(0);
-        let _ = self.right_motor.speed(0);
+    // Emergency stops the motors, then runs `park` (e.g. centring the steering),
+    // when no command arrives for `timeout`. Replaces any previous watchdog
+    pub fn watch(
+        &self,
+        timeout: Duration,
+        mut park: impl FnMut() -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let pair = Arc::downgrade(&self.inner);
+        let watchdog = Watchdog::spawn(timeout, move || {
+            let stopped = match pair.upgrade() {
+                Some(inner) => Ok(Motors { inner }.emergency_stop()?),
+                None => Ok(()),
+            };
+            stopped.and(park().context("PARKING FAILED"))
+        })?;
+        self.lock().watchdog = Some(watchdog);
+
+        Ok(())
     }
+}
+
+// Ticks the pair until its targets are reached, or it is dropped
+fn spawn_ramp(pair: Weak<Mutex<MotorPair>>) {
+    thread::spawn(move || loop {
+        sleep(RAMP_TICK);
+        let Some(pair) = pair.upgrade() else {
+            return;
+        };
+        let mut pair = pair.lock().unwrap_or_else(|e| e.into_inner());
+        match pair.tick() {
+            Ok(false) => {}
+            Ok(true) => {
+                pair.ticking = false;
+                return;
+            }
+            Err(error) => {
+                pair.fault = Some(error);
+                pair.ticking = false;
+                return;
+            }
+        }
+    });
+}
 
-    pub fn speed(&mut self, left_speed: i32, right_speed: i32) {
-        let _ = self.left_motor.speed(left_speed);
-        let _ = self.right_motor.speed(-right_speed); // Negating as per robot-hat python module
+#[pymethods]
+impl Motors {
+    #[new]
+    #[pyo3(signature = (freq = MOTOR_FREQ))]
+    pub fn new(freq: f64) -> Result<Self, PyError> {
+        let hat = RobotHat::shared().context("PWM I2C INIT FAILED")?;
+        Ok(Motors::with_hat(&hat, freq)?)
     }
 
-    pub fn forward(&mut self, speed: i32) {
-        self.speed(speed, speed);
+    // Ramps down like any other command, see emergency_stop()
+    pub fn stop(&self) -> Result<(), PyError> {
+        Ok(self.command(0.0, 0.0).context("MOTORS STOP FAILED")?)
     }
 
-    pub fn backward(&mut self, speed: i32) {
-        self.speed(-speed, -speed);
+    // Zero duty on both motors now, skipping the ramp
+    pub fn emergency_stop(&self) -> Result<(), PyError> {
+        let mut pair = self.lock();
+        pair.fault = None;
+        pair.target = [0.0; 2];
+        pair.output = [0.0; 2];
+
+        Ok(pair.write().context("MOTORS EMERGENCY STOP FAILED")?)
+    }
+
+    pub fn speed(&self, left_speed: i32, right_speed: i32) -> Result<(), PyError> {
+        Ok(self.command(left_speed as f64, right_speed as f64)?)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> Result<bool, PyError> {
+        self.emergency_stop()?;
+        Ok(false)
+    }
+
+    #[pyo3(signature = (timeout_ms = 500))]
+    pub fn enable_watchdog(&self, timeout_ms: u64) -> Result<(), PyError> {
+        Ok(self.watch(Duration::from_millis(timeout_ms), || Ok(()))?)
     }
 
-    pub fn turn_left(&mut self, speed: i32) {
-        self.speed(-speed, speed);
+    pub fn disable_watchdog(&self) {
+        self.lock().watchdog = None;
     }
 
-    pub fn turn_right(&mut self, speed: i32) {
-        self.speed(speed, -speed);
+    // Refreshes the watchdog without a new command, i.e. "keep going"
+    pub fn feed(&self) {
+        if let Some(watchdog) = &self.lock().watchdog {
+            watchdog.feed();
+        }
+    }
+
+    // None unless enable_watchdog() was called
+    #[getter]
+    pub fn watchdog(&self) -> Option<WatchdogStatus> {
+        self.lock().watchdog.as_ref().map(Watchdog::status)
+    }
+
+    // Told the mean output speed on every write, see Vehicle.enable_odometry()
+    #[getter]
+    pub fn odometry(&self) -> Option<Odometry> {
+        self.lock().odometry.clone()
+    }
+
+    #[setter]
+    pub fn set_odometry(&self, odometry: Option<Odometry>) {
+        self.lock().odometry = odometry;
+    }
+
+    #[getter]
+    pub fn ramp(&self) -> RampConfig {
+        self.lock().ramp
+    }
+
+    #[setter]
+    pub fn set_ramp(&self, ramp: RampConfig) -> Result<(), PyError> {
+        ramp.validate()?;
+        self.lock().ramp = ramp;
+
+        Ok(())
+    }
+
+    // Tick driven ramping (RampConfig.background = False), returns True once settled
+    #[pyo3(name = "tick")]
+    fn py_tick(&self) -> Result<bool, PyError> {
+        Ok(self.lock().tick()?)
+    }
+
+    // Commanded (left, right) speeds
+    #[getter]
+    pub fn target(&self) -> (f64, f64) {
+        self.lock().target()
+    }
+
+    // Speeds currently sent, lagging the target while ramping
+    #[getter]
+    pub fn output(&self) -> (f64, f64) {
+        self.lock().output()
+    }
+
+    #[getter]
+    pub fn calibration(&self) -> MotorsCalibration {
+        let pair = self.lock();
+        MotorsCalibration {
+            left: pair.left_motor.calibration,
+            right: pair.right_motor.calibration,
+        }
+    }
+
+    #[setter]
+    pub fn set_calibration(&self, calibration: MotorsCalibration) -> Result<(), PyError> {
+        calibration.validate()?;
+        let mut pair = self.lock();
+        pair.left_motor.calibration = calibration.left;
+        pair.right_motor.calibration = calibration.right;
+
+        Ok(())
+    }
+
+    #[pyo3(signature = (path = MOTOR_CALIBRATION_PATH))]
+    pub fn load_calibration(&self, path: &str) -> Result<(), PyError> {
+        self.set_calibration(MotorsCalibration::load(path)?)
+    }
+
+    #[pyo3(signature = (path = MOTOR_CALIBRATION_PATH))]
+    pub fn save_calibration(&self, path: &str) -> Result<(), PyError> {
+        self.calibration().save(path)
+    }
+
+    pub fn forward(&self, speed: i32) -> Result<(), PyError> {
+        self.speed(speed, speed)
+    }
+
+    pub fn backward(&self, speed: i32) -> Result<(), PyError> {
+        self.speed(-speed, -speed)
+    }
+
+    pub fn turn_left(&self, speed: i32) -> Result<(), PyError> {
+        self.speed(-speed, speed)
+    }
+
+    pub fn turn_right(&self, speed: i32) -> Result<(), PyError> {
+        self.speed(speed, -speed)
     }
 }
 
+// Maps a requested servo angle to the pulse width of one servo
 #[pyclass]
-pub struct Servo {
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServoCalibration {
+    #[pyo3(get, set)]
+    pub offset: i32, // added after inversion, e.g. -4 when angle 0 points 4 deg right
+    #[pyo3(get, set)]
+    pub min_angle: i32, // requested angles are clamped to min_angle..max_angle
+    #[pyo3(get, set)]
+    pub max_angle: i32,
+    #[pyo3(get, set)]
+    pub inverted: bool,
+    #[pyo3(get, set)]
+    pub min_pw: u16, // pulse width (us) at -90 deg
+    #[pyo3(get, set)]
+    pub max_pw: u16, // pulse width (us) at 90 deg
+}
+
+impl Default for ServoCalibration {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            min_angle: -90,
+            max_angle: 90,
+            inverted: false,
+            min_pw: MIN_PW,
+            max_pw: MAX_PW,
+        }
+    }
+}
+
+#[pymethods]
+impl ServoCalibration {
+    #[new]
+    #[pyo3(signature = (
+        offset = 0,
+        min_angle = -90,
+        max_angle = 90,
+        inverted = false,
+        min_pw = MIN_PW,
+        max_pw = MAX_PW
+    ))]
+    pub fn new(
+        offset: i32,
+        min_angle: i32,
+        max_angle: i32,
+        inverted: bool,
+        min_pw: u16,
+        max_pw: u16,
+    ) -> Self {
+        Self {
+            offset,
+            min_angle,
+            max_angle,
+            inverted,
+            min_pw,
+            max_pw,
+        }
+    }
+
+    // Requested angle within the mechanical limits
+    pub fn limit(&self, angle: i32) -> i32 {
+        angle.clamp(self.min_angle, self.max_angle)
+    }
+
+    // Pulse width (us) for a requested angle
+    pub fn pw_time(&self, angle: i32) -> i32 {
+        let angle = self.limit(angle);
+        let angle = if self.inverted { -angle } else { angle } + self.offset;
+        map_range(
+            (-90, 90),
+            (self.min_pw.into(), self.max_pw.into()),
+            angle.clamp(-90, 90),
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl ServoCalibration {
+    pub fn validate(&self) -> Result<()> {
+        if !(-90 <= self.min_angle && self.min_angle < self.max_angle && self.max_angle <= 90) {
+            bail!(Error::Calibration(format!(
+                "SERVO LIMITS {}..{} MUST BE ASCENDING WITHIN -90..90",
+                self.min_angle, self.max_angle
+            )));
+        }
+        if self.offset.abs() > 90 {
+            bail!(Error::Calibration(format!(
+                "SERVO OFFSET {} OUT OF RANGE (-90..90)",
+                self.offset
+            )));
+        }
+        if self.min_pw >= self.max_pw {
+            bail!(Error::Calibration(format!(
+                "SERVO PULSE WIDTHS {}..{} MUST BE ASCENDING",
+                self.min_pw, self.max_pw
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// Servo calibrations keyed by PWM channel name, e.g. [P2] for the steering servo.
+// Channels without an entry are left uncalibrated
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ServosCalibration {
+    #[pyo3(get, set)]
+    pub channels: BTreeMap<String, ServoCalibration>,
+}
+
+#[pymethods]
+impl ServosCalibration {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, channel: u8) -> ServoCalibration {
+        self.channels
+            .get(&format!("P{channel}"))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, channel: u8, calibration: ServoCalibration) -> Result<(), PyError> {
+        Channel::new(channel)?;
+        calibration.validate()?;
+        self.channels.insert(format!("P{channel}"), calibration);
+
+        Ok(())
+    }
+
+    // Installs the calibration of `servo`'s channel
+    pub fn apply(&self, servo: &Servo) -> Result<(), PyError> {
+        servo.set_calibration(self.get(servo.channel()))
+    }
+
+    #[staticmethod]
+    pub fn load(path: &str) -> Result<Self, PyError> {
+        Ok(read_servo_calibration(Path::new(path))?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), PyError> {
+        let text = toml::to_string(self).context("SERIALISING SERVO CALIBRATION")?;
+        fs::write(path, text)
+            .map_err(Error::from)
+            .with_context(|| format!("WRITING SERVO CALIBRATION {path}"))?;
+
+        Ok(())
+    }
+}
+
+impl ServosCalibration {
+    // `path` if it exists, otherwise no calibration
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        read_servo_calibration(path)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, calibration) in &self.channels {
+            let channel = name
+                .strip_prefix('P')
+                .and_then(|index| index.parse().ok())
+                .ok_or_else(|| Error::Calibration(format!("{name} IS NOT A PWM CHANNEL")))?;
+            Channel::new(channel)?;
+            calibration
+                .validate()
+                .with_context(|| format!("SERVO {name}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_servo_calibration(path: &Path) -> Result<ServosCalibration> {
+    let text = fs::read_to_string(path)
+        .map_err(Error::from)
+        .with_context(|| format!("READING SERVO CALIBRATION {}", path.display()))?;
+    let calibration: ServosCalibration = toml::from_str(&text)
+        .map_err(Error::from)
+        .with_context(|| format!("PARSING SERVO CALIBRATION {}", path.display()))?;
+    calibration
+        .validate()
+        .with_context(|| format!("INVALID SERVO CALIBRATION {}", path.display()))?;
+
+    Ok(calibration)
+}
+
+// Trapezoidal move towards `target`: accelerate, cruise at `max_speed`, brake in time.
+// Re-planned every tick from the current velocity, so a new target mid-move stays smooth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoProfile {
+    pub target: f64,    // deg
+    pub max_speed: f64, // deg/s
+    pub accel: f64,     // deg/s^2, 0 for none (constant speed)
+}
+
+impl ServoProfile {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.max_speed.is_finite() && self.max_speed > 0.0) {
+            bail!(Error::invalid("SERVO MAX SPEED MUST BE ABOVE 0"));
+        }
+        if !(self.accel.is_finite() && self.accel >= 0.0) {
+            bail!(Error::invalid("SERVO ACCELERATION MUST BE 0 OR ABOVE"));
+        }
+
+        Ok(())
+    }
+
+    // Advances `position` and `velocity` by `dt`, returns true once at the target
+    pub fn step(&self, position: &mut f64, velocity: &mut f64, dt: f64) -> bool {
+        let remaining = self.target - *position;
+        if remaining == 0.0 && *velocity == 0.0 {
+            return true;
+        }
+
+        let direction = remaining.signum();
+        let mut speed = *velocity * direction; // towards the target
+        if self.accel <= 0.0 {
+            speed = self.max_speed;
+        } else if speed > 0.0 && speed * speed / (2.0 * self.accel) >= remaining.abs() {
+            speed = (speed - self.accel * dt).max(0.0);
+        } else {
+            speed = (speed + self.accel * dt).min(self.max_speed);
+        }
+
+        // a step that would reach (or cross) the target ends the move there
+        let travel = speed * dt;
+        if travel >= remaining.abs() || (speed <= 0.0 && remaining.abs() < 1e-6) {
+            *position = self.target;
+            *velocity = 0.0;
+            return true;
+        }
+        *position += travel * direction;
+        *velocity = speed * direction;
+
+        false
+    }
+}
+
+// One servo channel; dropped (and parked) with its last Servo handle
+pub struct ServoState {
     pwm: PWM,
+    timing: PwmTiming,
+    calibration: ServoCalibration,
+    park_angle: i32,
+    park: ParkGuard,
+    angle: Option<i32>,         // last commanded, None until the first angle()
+    odometry: Option<Odometry>, // told every angle when this servo steers
+    profile: Option<ServoProfile>,
+    position: f64, // profile position (deg), between the whole angles sent
+    velocity: f64, // deg/s
+    last_tick: Option<Instant>,
+    ticking: bool,                // background ticker running
+    fault: Option<anyhow::Error>, // last error of the ticker, returned by the next move_to()
+}
+
+impl ServoState {
+    // pulse width in timer counts: pw_time / signal period (20,000 us at 50Hz)
+    fn counts(&self, pw_time: i32) -> u16 {
+        let period_us = 1_000_000.0 / self.timing.frequency;
+        let value = (pw_time as f64 * self.timing.period as f64 / period_us).round();
+        value.clamp(0.0, u16::MAX as f64) as u16
+    }
+
+    fn park_command(&self) -> Command {
+        let counts = self.counts(self.calibration.pw_time(self.park_angle));
+        Command::pulse_width(self.pwm.channel, counts)
+    }
+
+    pub fn pulse_width_time(&mut self, pw_time: i32) -> Result<()> {
+        let counts = self.counts(pw_time);
+        Ok(self.pwm.pulse_width(counts)?)
+    }
+
+    // Straight to `angle`, ending any move in progress
+    pub fn angle(&mut self, angle: i32) -> Result<()> {
+        self.profile = None;
+        self.velocity = 0.0;
+        self.position = self.calibration.limit(angle) as f64;
+        self.write(angle)
+    }
+
+    fn write(&mut self, angle: i32) -> Result<()> {
+        let angle = self.calibration.limit(angle);
+        self.angle = Some(angle);
+        if let Some(odometry) = &self.odometry {
+            odometry.lock().set_steering(angle as f64);
+        }
+        self.pulse_width_time(self.calibration.pw_time(angle))
+            .with_context(|| format!("SERVO ANGLE {angle} FAILED"))
+    }
+
+    pub fn commanded(&self) -> Option<i32> {
+        self.angle
+    }
+
+    pub fn profile(&self) -> Option<ServoProfile> {
+        self.profile
+    }
+
+    // Follows the profile for `dt`, sending the angle whenever it reaches a new whole degree.
+    // Returns true once no move is in progress
+    pub fn advance(&mut self, dt: Duration) -> Result<bool> {
+        let Some(profile) = self.profile else {
+            return Ok(true);
+        };
+        let arrived = profile.step(&mut self.position, &mut self.velocity, dt.as_secs_f64());
+        if arrived {
+            self.profile = None;
+        }
+
+        let angle = self.position.round() as i32;
+        if self.angle != Some(angle) {
+            if let Err(error) = self.write(angle) {
+                self.profile = None;
+                self.velocity = 0.0;
+                return Err(error);
+            }
+        }
+
+        Ok(arrived)
+    }
+
+    // Advances by the time since the last tick
+    pub fn tick(&mut self) -> Result<bool> {
+        let now = Instant::now();
+        let dt = now - self.last_tick.unwrap_or(now);
+        self.last_tick = Some(now);
+
+        self.advance(dt)
+    }
+
+    pub fn park(&mut self) -> Result<()> {
+        self.angle(self.park_angle).context("SERVO PARK FAILED")
+    }
+}
+
+impl Drop for ServoState {
+    fn drop(&mut self) {
+        let _ = self.park();
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct Servo {
+    inner: Arc<Mutex<ServoState>>,
+    settled: Arc<Condvar>, // a move ended, see wait_settled()
+}
+
+impl Servo {
+    pub fn with_pwm(mut pwm: PWM, freq: f64) -> Result<Self> {
+        let timing = pwm.freq(freq).context("SERVO FREQ INIT FAILED")?;
+        let park = ParkGuard::register(pwm.hat.clone(), Command::pulse_width(pwm.channel, 0));
+        let state = ServoState {
+            pwm,
+            timing,
+            calibration: ServoCalibration::default(),
+            park_angle: 0,
+            park,
+            angle: None,
+            odometry: None,
+            profile: None,
+            position: 0.0,
+            velocity: 0.0,
+            last_tick: None,
+            ticking: false,
+            fault: None,
+        };
+        state.park.update(state.park_command());
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(state)),
+            settled: Arc::new(Condvar::new()),
+        })
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, ServoState> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Starts a profiled move on the background ticker. A servo that was never
+    // commanded has no known position and goes straight there
+    pub fn move_profile(&self, angle: i32, max_speed: f64, accel: f64) -> Result<()> {
+        let mut state = self.lock();
+        if let Some(fault) = state.fault.take() {
+            return Err(fault.context("SERVO MOVE FAILED"));
+        }
+        let profile = ServoProfile {
+            target: state.calibration.limit(angle) as f64,
+            max_speed,
+            accel,
+        };
+        profile.validate()?;
+        if state.angle.is_none() {
+            return state.angle(angle);
+        }
+
+        if state.profile.is_none() {
+            state.last_tick = Some(Instant::now());
+        }
+        state.profile = Some(profile);
+        if !state.ticking {
+            state.ticking = true;
+            spawn_servo_ticker(Arc::downgrade(&self.inner), self.settled.clone());
+        }
+
+        Ok(())
+    }
+
+    // Blocks until no move is in progress, false on timeout
+    pub fn wait_settled(&self, timeout: Option<Duration>) -> Result<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.lock();
+        while state.profile.is_some() {
+            // polled as well, angle() ends a move without notifying
+            let mut wait = SERVO_TICK;
+            if let Some(deadline) = deadline {
+                match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) => wait = wait.min(left),
+                    None => return Ok(false),
+                }
+            }
+            state = self
+                .settled
+                .wait_timeout(state, wait)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        if let Some(fault) = state.fault.take() {
+            return Err(fault.context("SERVO MOVE FAILED"));
+        }
+
+        Ok(true)
+    }
+}
+
+// Ticks the servo until its move ends, or it is dropped
+fn spawn_servo_ticker(servo: Weak<Mutex<ServoState>>, settled: Arc<Condvar>) {
+    thread::spawn(move || loop {
+        sleep(SERVO_TICK);
+        let Some(servo) = servo.upgrade() else {
+            return;
+        };
+        let mut state = servo.lock().unwrap_or_else(|e| e.into_inner());
+        let done = match state.tick() {
+            Ok(done) => done,
+            Err(error) => {
+                state.fault = Some(error);
+                true
+            }
+        };
+        if done {
+            state.ticking = false;
+            settled.notify_all();
+            return;
+        }
+    });
 }
 
 #[pymethods]
 impl Servo {
     #[new]
-    pub fn new(pwm_pin: u8) -> Result<Self> {
-        let mut pwm = PWM::new(pwm_pin).context("PWM init failed")?;
-        pwm.period(4095)?; // ref: robot-hat
-        pwm.prescaler(351)?; // ref: robot-hat --> (CPU_CLOCK / FREQ / PERIOD )
+    #[pyo3(signature = (pwm_pin, freq = SERVO_FREQ))]
+    pub fn new(pwm_pin: u8, freq: f64) -> Result<Self, PyError> {
+        let pwm = PWM::new(pwm_pin).context("PWM init failed")?;
+        Ok(Servo::with_pwm(pwm, freq)?)
+    }
 
-        Ok(Self { pwm })
+    #[getter]
+    pub fn timing(&self) -> PwmTiming {
+        self.lock().timing
     }
 
-    pub fn pulse_width_time(&mut self, pw_time: i32) -> Result<()> {
-        let value = ((pw_time * 4095) / 20000) as u16; // 20,000 us --> 20ms (50Hz signal for servo)
-        self.pwm.pulse_width(value)?;
+    #[getter]
+    pub fn channel(&self) -> u8 {
+        self.lock().pwm.channel.index()
+    }
+
+    #[getter]
+    pub fn calibration(&self) -> ServoCalibration {
+        self.lock().calibration
+    }
+
+    // Takes effect from the next angle(), the park angle included
+    #[setter]
+    pub fn set_calibration(&self, calibration: ServoCalibration) -> Result<(), PyError> {
+        calibration.validate()?;
+        let mut state = self.lock();
+        state.calibration = calibration;
+        state.park.update(state.park_command());
 
         Ok(())
     }
 
-    pub fn angle(&mut self, angle: i32) {
-        let angle = angle.clamp(-90, 90);
-        let pw_time = map_range((-90, 90), (MIN_PW.into(), MAX_PW.into()), angle);
-        let pw_time = pw_time.clamp(MIN_PW.into(), MAX_PW.into());
-        let _ = self.pulse_width_time(pw_time);
+    pub fn pulse_width_time(&self, pw_time: i32) -> Result<(), PyError> {
+        Ok(self.lock().pulse_width_time(pw_time)?)
+    }
+
+    pub fn angle(&self, angle: i32) -> Result<(), PyError> {
+        Ok(self.lock().angle(angle)?)
+    }
+
+    // Eases to `angle` in the background: `max_speed` in deg/s, `accel` in deg/s^2 (0 for none)
+    #[pyo3(signature = (angle, max_speed = 120.0, accel = 600.0))]
+    pub fn move_to(&self, angle: i32, max_speed: f64, accel: f64) -> Result<(), PyError> {
+        Ok(self.move_profile(angle, max_speed, accel)?)
+    }
+
+    // Returns once the move is done, False on timeout
+    #[pyo3(signature = (timeout_s = None))]
+    pub fn wait(&self, py: Python<'_>, timeout_s: Option<f64>) -> Result<bool, PyError> {
+        let timeout = timeout_s
+            .map(|timeout_s| seconds("SERVO WAIT TIMEOUT", timeout_s))
+            .transpose()?;
+        Ok(py.allow_threads(|| self.wait_settled(timeout))?)
+    }
+
+    // Angle last sent (None before the first), trailing the target while moving
+    #[getter]
+    pub fn commanded(&self) -> Option<i32> {
+        self.lock().commanded()
+    }
+
+    // Where the current move ends, the commanded angle when idle
+    #[getter]
+    pub fn target(&self) -> Option<i32> {
+        let state = self.lock();
+        match state.profile {
+            Some(profile) => Some(profile.target as i32),
+            None => state.angle,
+        }
+    }
+
+    #[getter]
+    pub fn moving(&self) -> bool {
+        self.lock().profile.is_some()
+    }
+
+    // Where the servo goes on exit, drop and shutdown
+    #[getter]
+    pub fn park_angle(&self) -> i32 {
+        self.lock().park_angle
+    }
+
+    #[setter]
+    pub fn set_park_angle(&self, angle: i32) {
+        let mut state = self.lock();
+        state.park_angle = angle.clamp(-90, 90);
+        state.park.update(state.park_command());
+    }
+
+    pub fn park(&self) -> Result<(), PyError> {
+        Ok(self.lock().park()?)
+    }
+
+    // Set on the steering servo only, its angle is taken as the front wheel angle
+    #[getter]
+    pub fn odometry(&self) -> Option<Odometry> {
+        self.lock().odometry.clone()
+    }
+
+    #[setter]
+    pub fn set_odometry(&self, odometry: Option<Odometry>) {
+        self.lock().odometry = odometry;
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> Result<bool, PyError> {
+        self.park()?;
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockBus, MockPin};
+
+    fn mock_pwm(channel: u8, mock: &MockBus) -> PWM {
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        hat.pwm(channel).unwrap()
+    }
+
+    #[test]
+    fn servo_angle_maps_to_pulse_width() {
+        let mock = MockBus::new();
+        let servo = Servo::with_pwm(mock_pwm(2, &mock), SERVO_FREQ).unwrap();
+
+        for (angle, expected) in [
+            (0, 4500),
+            (-90, 1500),
+            (90, 7500),
+            (200, 7500),
+            (-200, 1500),
+        ] {
+            servo.angle(angle).unwrap();
+
+            let write = mock.last_word_write().unwrap();
+            assert_eq!(write.register, 0x22);
+            assert_eq!(write.mcu_value(), expected);
+        }
+    }
+
+    #[test]
+    fn servo_calibration_offsets_inverts_and_limits() {
+        let mock = MockBus::new();
+        let servo = Servo::with_pwm(mock_pwm(2, &mock), SERVO_FREQ).unwrap();
+        let calibration = ServoCalibration::new(-4, -35, 35, true, 600, 2400);
+        servo.set_calibration(calibration).unwrap();
+
+        // 10 us per degree from 600 us at -90
+        for (angle, expected) in [(0, 4380), (50, 3330), (-90, 5430)] {
+            servo.angle(angle).unwrap();
+            assert_eq!(mock.last_word_write().unwrap().mcu_value(), expected);
+        }
+        assert_eq!(servo.lock().commanded(), Some(-35));
+
+        servo.park().unwrap();
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 4380);
+        let reversed = ServoCalibration::new(0, 40, -40, false, 500, 2500);
+        assert!(servo.set_calibration(reversed).is_err());
+    }
+
+    #[test]
+    fn servo_profile_is_trapezoidal() {
+        let profile = ServoProfile {
+            target: 90.0,
+            max_speed: 100.0,
+            accel: 200.0,
+        };
+        let (mut position, mut velocity) = (0.0, 0.0);
+        let mut trace = vec![];
+        while !profile.step(&mut position, &mut velocity, 0.01) {
+            trace.push((position, velocity));
+        }
+
+        // 0.5 s up to 100 deg/s (25 deg), 0.4 s cruise, 0.5 s down
+        assert!((136..=141).contains(&trace.len()), "{}", trace.len());
+        assert!((trace[24].0 - 6.25).abs() < 1.0);
+        assert_eq!(trace[70].1, 100.0);
+        assert!(trace.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(trace.iter().all(|&(_, v)| v <= 100.0));
+        assert!(trace.last().unwrap().1 < 5.0);
+        assert_eq!((position, velocity), (90.0, 0.0));
+    }
+
+    #[test]
+    fn servo_moves_in_the_background() {
+        let mock = MockBus::new();
+        let servo = Servo::with_pwm(mock_pwm(2, &mock), SERVO_FREQ).unwrap();
+        assert_eq!(servo.commanded(), None);
+        servo.move_profile(-30, 90.0, 0.0).unwrap(); // unknown position, jumps
+        assert_eq!(servo.commanded(), Some(-30));
+        mock.clear();
+
+        servo.move_profile(30, 600.0, 3000.0).unwrap();
+        assert!(servo.moving());
+        assert_eq!(servo.target(), Some(30));
+        assert!(servo.wait_settled(Some(Duration::from_secs(1))).unwrap());
+        assert_eq!(servo.commanded(), Some(30));
+
+        let pulses: Vec<u16> = mock.word_writes().iter().map(|w| w.mcu_value()).collect();
+        assert!(pulses.len() > 3);
+        assert!(pulses.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(pulses.last(), Some(&5499)); // 30 deg
+
+        servo.move_profile(-90, 10.0, 0.0).unwrap();
+        sleep(Duration::from_millis(50));
+        servo.angle(0).unwrap();
+        assert!(!servo.moving());
+        assert!(servo.wait_settled(Some(Duration::ZERO)).unwrap());
+        assert!(servo.move_profile(0, 0.0, 10.0).is_err());
+    }
+
+    #[test]
+    fn motor_speed_sets_duty_and_direction() {
+        let mock = MockBus::new();
+        let pin = MockPin::new();
+        let mut motor =
+            Motor::with_parts(mock_pwm(12, &mock), Box::new(pin.clone()), MOTOR_FREQ).unwrap();
+
+        motor.speed(-50).unwrap();
+        let write = mock.last_word_write().unwrap();
+        assert_eq!(write.register, 0x2C);
+        assert_eq!(write.mcu_value(), 29999);
+        assert_eq!(pin.level(), Some(Level::Low));
+
+        motor.speed(100).unwrap();
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 59999);
+        assert_eq!(pin.level(), Some(Level::High));
+    }
+
+    fn mock_motors(mock: &MockBus) -> (Motors, MockPin, MockPin) {
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let left_pin = MockPin::new();
+        let right_pin = MockPin::new();
+        let left = Motor::with_parts(hat.pwm(12).unwrap(), Box::new(left_pin.clone()), MOTOR_FREQ)
+            .unwrap();
+        let right = Motor::with_parts(
+            hat.pwm(13).unwrap(),
+            Box::new(right_pin.clone()),
+            MOTOR_FREQ,
+        )
+        .unwrap();
+
+        (Motors::with_motors(left, right), left_pin, right_pin)
+    }
+
+    #[test]
+    fn motors_negate_right_speed() {
+        let (motors, left_pin, right_pin) = mock_motors(&MockBus::new());
+
+        motors.forward(30).unwrap();
+        assert_eq!(left_pin.level(), Some(Level::High));
+        assert_eq!(right_pin.level(), Some(Level::Low));
+    }
+
+    #[test]
+    fn ramp_limits_duty_change_per_second() {
+        let mock = MockBus::new();
+        let (motors, _, _) = mock_motors(&mock);
+        motors.set_ramp(RampConfig::new(100.0, false)).unwrap();
+        mock.clear();
+
+        motors.forward(100).unwrap();
+        assert!(mock.word_writes().is_empty()); // only a target until ticked
+        assert_eq!(motors.target(), (100.0, 100.0));
+
+        let mut pair = motors.lock();
+        assert!(!pair.advance(Duration::from_millis(250)).unwrap());
+        assert_eq!(pair.output(), (25.0, 25.0));
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 14999); // right motor, 25%
+        assert!(pair.advance(Duration::from_secs(1)).unwrap());
+        assert_eq!(pair.output(), (100.0, 100.0));
+        drop(pair);
+
+        motors.backward(100).unwrap();
+        motors.lock().advance(Duration::from_millis(500)).unwrap();
+        assert_eq!(motors.output(), (50.0, 50.0));
+    }
+
+    #[test]
+    fn emergency_stop_skips_the_ramp() {
+        let mock = MockBus::new();
+        let (motors, _, _) = mock_motors(&mock);
+        motors.set_ramp(RampConfig::new(50.0, false)).unwrap();
+        motors.forward(60).unwrap();
+        motors.lock().advance(Duration::from_secs(2)).unwrap();
+
+        motors.stop().unwrap();
+        assert_eq!(motors.output(), (60.0, 60.0));
+
+        motors.emergency_stop().unwrap();
+        assert_eq!(motors.output(), (0.0, 0.0));
+        assert_eq!(motors.target(), (0.0, 0.0));
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 0);
+        assert!(motors.set_ramp(RampConfig::new(-1.0, true)).is_err());
+    }
+
+    #[test]
+    fn background_ramp_reaches_target() {
+        let (motors, _, _) = mock_motors(&MockBus::new());
+        motors.set_ramp(RampConfig::new(1000.0, true)).unwrap();
+
+        motors.forward(50).unwrap();
+        for _ in 0..100 {
+            if motors.lock().settled() {
+                break;
+            }
+            sleep(RAMP_TICK);
+        }
+        assert_eq!(motors.output(), (50.0, 50.0));
+    }
+
+    #[test]
+    fn calibration_inverts_trims_and_skips_deadband() {
+        let calibration = MotorCalibration::new(true, 0.5, 20);
+
+        assert_eq!(calibration.duty(0), 0);
+        assert_eq!(calibration.duty(100), -60);
+        assert_eq!(calibration.duty(-100), 60);
+        assert_eq!(calibration.duty(1), -20);
+        assert_eq!(MotorCalibration::new(false, 1.5, 0).duty(80), 100);
+        assert_eq!(MotorCalibration::default().duty(-250), -100);
+    }
+
+    #[test]
+    fn calibrated_motor_remaps_duty() {
+        let mock = MockBus::new();
+        let pin = MockPin::new();
+        let mut motor =
+            Motor::with_parts(mock_pwm(12, &mock), Box::new(pin.clone()), MOTOR_FREQ).unwrap();
+        motor
+            .set_calibration(MotorCalibration::new(false, 1.0, 40))
+            .unwrap();
+
+        motor.speed(50).unwrap();
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 41999);
+        assert_eq!(pin.level(), Some(Level::High));
+        assert!(motor
+            .set_calibration(MotorCalibration::new(false, 0.0, 0))
+            .is_err());
+    }
+
+    #[test]
+    fn calibration_file_round_trips() {
+        let path = std::env::temp_dir().join(format!("motors-{}.toml", std::process::id()));
+        let mut calibration = MotorsCalibration::default();
+        calibration.left.trim = 0.9;
+        calibration.right.min_duty = 15;
+        calibration.save(path.to_str().unwrap()).unwrap();
+
+        let loaded = MotorsCalibration::load_or_default(&path).unwrap();
+        assert_eq!(loaded, calibration);
+        assert!(loaded.right.inverted);
+
+        fs::write(&path, "[left]\nmin_duty = 100\n").unwrap();
+        let error = PyError::from(MotorsCalibration::load_or_default(&path).unwrap_err());
+        assert!(matches!(error.kind(), Some(Error::Calibration(_))));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            MotorsCalibration::load_or_default(&path).unwrap(),
+            MotorsCalibration::default()
+        );
+    }
+
+    #[test]
+    fn servo_calibration_file_is_keyed_by_channel() {
+        let path = std::env::temp_dir().join(format!("servos-{}.toml", std::process::id()));
+        let mut calibration = ServosCalibration::new();
+        let steering = ServoCalibration::new(-4, -35, 35, false, 500, 2500);
+        calibration.set(2, steering).unwrap();
+        assert!(calibration.set(20, steering).is_err());
+        calibration.save(path.to_str().unwrap()).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("[P2]"));
+
+        let loaded = ServosCalibration::load_or_default(&path).unwrap();
+        assert_eq!(loaded, calibration);
+        assert_eq!(loaded.get(2), steering);
+        assert_eq!(loaded.get(0), ServoCalibration::default());
+
+        let mock = MockBus::new();
+        let servo = Servo::with_pwm(mock_pwm(2, &mock), SERVO_FREQ).unwrap();
+        loaded.apply(&servo).unwrap();
+        assert_eq!(servo.calibration(), steering);
+
+        for text in [
+            "[Q2]\noffset = 1\n",
+            "[P2]\nmin_angle = 10\nmax_angle = 5\n",
+        ] {
+            fs::write(&path, text).unwrap();
+            let error = PyError::from(ServosCalibration::load_or_default(&path).unwrap_err());
+            assert!(matches!(error.kind(), Some(Error::Calibration(_))));
+        }
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            ServosCalibration::load_or_default(&path).unwrap(),
+            ServosCalibration::default()
+        );
+    }
+
+    #[test]
+    fn servo_parks_on_drop_and_shutdown() {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let servo = Servo::with_pwm(hat.pwm(1).unwrap(), SERVO_FREQ).unwrap();
+        servo.angle(60).unwrap();
+        servo.set_park_angle(-45);
+
+        crate::shutdown::park_hat(&hat).unwrap();
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 3000); // 1000 us
+
+        servo.angle(60).unwrap();
+        drop(servo);
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 3000);
+    }
+
+    #[test]
+    fn dropped_motors_stop() {
+        let mock = MockBus::new();
+        let (motors, _, _) = mock_motors(&mock);
+        motors.forward(80).unwrap();
+
+        drop(motors);
+        let writes = mock.word_writes();
+        let last: Vec<(u8, u16)> = writes[writes.len() - 2..]
+            .iter()
+            .map(|w| (w.register, w.mcu_value()))
+            .collect();
+        assert_eq!(last, vec![(0x2C, 0), (0x2D, 0)]);
     }
 }
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use rppal::gpio::{InputPin, Level, OutputPin};
+use rppal::i2c::I2c;
+
+use crate::error::Error;
+
+// Minimal SMBus surface used by the Robot HAT drivers, so they can run against a mock off the Pi
+pub trait I2cBus: Send {
+    fn set_slave_address(&mut self, address: u16) -> Result<()>;
+    fn smbus_quick_command(&mut self, command: bool) -> Result<()>;
+    fn smbus_receive_byte(&mut self) -> Result<u8>;
+    fn smbus_send_byte(&mut self, value: u8) -> Result<()>;
+    // SMBus order: low byte first on the wire
+    fn smbus_write_word(&mut self, command: u8, value: u16) -> Result<()>;
+    // Swapped order: high byte first on the wire
+    fn smbus_write_word_swapped(&mut self, command: u8, value: u16) -> Result<()>;
+    fn smbus_read_word(&mut self, command: u8) -> Result<u16>;
+}
+
+// errno the kernel reports when nothing acks the address (ENXIO, EREMOTEIO)
+const NO_ACK_ERRNOS: [i32; 2] = [6, 121];
+
+// rppal bus that remembers the selected address, so a device that never answers is reported
+// as DeviceNotFound rather than a bare bus error. Later failures stay Bus: the MCU acked
+// before, so they are transient and left to the retry policy
+pub struct LinuxI2c {
+    i2c: I2c,
+    address: Option<u16>,
+    answered: bool,
+}
+
+impl LinuxI2c {
+    pub fn new(i2c: I2c) -> Self {
+        Self {
+            i2c,
+            address: None,
+            answered: false,
+        }
+    }
+
+    fn check<T>(&mut self, result: rppal::i2c::Result<T>, what: &'static str) -> Result<T> {
+        match result {
+            Ok(value) => {
+                self.answered = true;
+                Ok(value)
+            }
+            Err(e) => Err(bus_error(e, self.address.filter(|_| !self.answered))).context(what),
+        }
+    }
+}
+
+// A no-ack errno against a device that has not answered yet means nothing is at `address`
+fn bus_error(error: rppal::i2c::Error, address: Option<u16>) -> Error {
+    match (&error, address) {
+        (rppal::i2c::Error::Io(io), Some(address))
+            if io
+                .raw_os_error()
+                .is_some_and(|errno| NO_ACK_ERRNOS.contains(&errno)) =>
+        {
+            Error::DeviceNotFound(address)
+        }
+        _ => Error::from(error),
+    }
+}
+
+impl I2cBus for LinuxI2c {
+    fn set_slave_address(&mut self, address: u16) -> Result<()> {
+        self.address = Some(address);
+        self.answered = false;
+        self.i2c
+            .set_slave_address(address)
+            .map_err(|e| bus_error(e, Some(address)))
+            .context("I2C SET SLAVE ADDRESS FAILED")
+    }
+
+    fn smbus_quick_command(&mut self, command: bool) -> Result<()> {
+        let result = self.i2c.smbus_quick_command(command);
+        self.check(result, "I2C QUICK COMMAND FAILED")
+    }
+
+    fn smbus_receive_byte(&mut self) -> Result<u8> {
+        let result = self.i2c.smbus_receive_byte();
+        self.check(result, "I2C RECEIVE BYTE FAILED")
+    }
+
+    fn smbus_send_byte(&mut self, value: u8) -> Result<()> {
+        let result = self.i2c.smbus_send_byte(value);
+        self.check(result, "I2C SEND BYTE FAILED")
+    }
+
+    fn smbus_write_word(&mut self, command: u8, value: u16) -> Result<()> {
+        let result = self.i2c.smbus_write_word(command, value);
+        self.check(result, "I2C WRITE WORD FAILED")
+    }
+
+    fn smbus_write_word_swapped(&mut self, command: u8, value: u16) -> Result<()> {
+        let result = self.i2c.smbus_write_word_swapped(command, value);
+        self.check(result, "I2C WRITE WORD FAILED")
+    }
+
+    fn smbus_read_word(&mut self, command: u8) -> Result<u16> {
+        let result = self.i2c.smbus_read_word(command);
+        self.check(result, "I2C READ WORD FAILED")
+    }
+}
+
+// Digital output used for motor direction pins
+pub trait DigitalOutput: Send {
+    fn write(&mut self, level: Level);
+}
+
+impl DigitalOutput for OutputPin {
+    fn write(&mut self, level: Level) {
+        OutputPin::write(self, level);
+    }
+}
+
+// Digital input used for board straps such as BOARD_TYPE
+pub trait DigitalInput: Send {
+    fn read(&self) -> Level;
+}
+
+impl DigitalInput for InputPin {
+    fn read(&self) -> Level {
+        InputPin::read(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+    fn errno(code: i32) -> rppal::i2c::Error {
+        rppal::i2c::Error::Io(io::Error::from_raw_os_error(code))
+    }
+
+    #[test]
+    fn unanswered_address_is_device_not_found() {
+        assert!(matches!(
+            bus_error(errno(121), Some(0x14)),
+            Error::DeviceNotFound(0x14)
+        ));
+        assert!(matches!(
+            bus_error(errno(6), Some(0x14)),
+            Error::DeviceNotFound(0x14)
+        ));
+    }
+
+    #[test]
+    fn other_failures_stay_bus_errors() {
+        // EIO, or a no-ack from a device that already answered
+        assert!(matches!(bus_error(errno(5), Some(0x14)), Error::Bus(_)));
+        assert!(matches!(bus_error(errno(121), None), Error::Bus(_)));
+    }
+}
@@ -0,0 +1,394 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+    thread::sleep,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    adc::Adc,
+    board,
+    error::PyError,
+    hal::I2cBus,
+    mcu_init, open_bus,
+    protocol::{Channel, Command, Register, Timer},
+    scan::{probe_bus, I2cDevice, ProbeMode},
+    PWM,
+};
+
+static SHARED: Mutex<Option<RobotHat>> = Mutex::new(None);
+
+const MAX_EVENTS: usize = 64; // oldest recovery events are dropped past this
+
+type ResetFn = Box<dyn FnMut() -> Result<()> + Send>;
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    #[pyo3(get, set)]
+    pub attempts: u32, // tries before the MCU is reset
+    #[pyo3(get, set)]
+    pub backoff_ms: u64, // doubled after every failed try
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff_ms: 2,
+        }
+    }
+}
+
+#[pymethods]
+impl RetryPolicy {
+    #[new]
+    #[pyo3(signature = (attempts = 3, backoff_ms = 2))]
+    pub fn new(attempts: u32, backoff_ms: u64) -> Self {
+        Self {
+            attempts,
+            backoff_ms,
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryKind {
+    Retry, // a transaction failed and was retried
+    Reset, // the MCU was reset and the shadow registers replayed
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryEvent {
+    #[pyo3(get)]
+    pub kind: RecoveryKind,
+    #[pyo3(get)]
+    pub attempt: u32,
+    #[pyo3(get)]
+    pub replayed: usize, // registers written back after a reset
+    #[pyo3(get)]
+    pub error: String, // the bus error that triggered it
+}
+
+#[pymethods]
+impl RecoveryEvent {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+// Last value written to every PWM register, as the MCU forgets them on reset
+#[derive(Debug, Default)]
+pub struct Shadow {
+    registers: BTreeMap<Register, u16>,
+}
+
+impl Shadow {
+    fn record(&mut self, command: Command) {
+        if !matches!(command.register, Register::Adc(_)) {
+            self.registers.insert(command.register, command.value);
+        }
+    }
+
+    // Period register (ARR) of `timer`, 0 until one was written
+    pub fn period(&self, timer: Timer) -> u16 {
+        self.registers
+            .get(&Register::Period(timer))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn pulse_width(&self, channel: Channel) -> Option<u16> {
+        self.registers.get(&Register::PulseWidth(channel)).copied()
+    }
+
+    pub fn commands(&self) -> Vec<Command> {
+        self.registers
+            .iter()
+            .map(|(&register, &value)| Command { register, value })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.registers.clear();
+    }
+}
+
+pub struct HatBus {
+    pub bus: Box<dyn I2cBus>,
+    pub shadow: Shadow,
+    pub retry: RetryPolicy,
+    reset: Option<ResetFn>,
+    events: VecDeque<RecoveryEvent>,
+}
+
+impl HatBus {
+    // Runs `transaction`, retrying bus errors with backoff and resetting the MCU as a last resort
+    pub fn transact<T>(
+        &mut self,
+        mut transaction: impl FnMut(&mut dyn I2cBus) -> Result<T>,
+    ) -> Result<T> {
+        let attempts = self.retry.attempts.max(1);
+        let mut backoff = Duration::from_millis(self.retry.backoff_ms);
+        let mut last_error = String::new();
+        for attempt in 1..=attempts {
+            match transaction(self.bus.as_mut()) {
+                Ok(value) => return Ok(value),
+                Err(error) => {
+                    last_error = format!("{error:#}");
+                    self.report(RecoveryKind::Retry, attempt, 0, last_error.clone());
+                    sleep(backoff);
+                    backoff *= 2;
+                }
+            }
+        }
+
+        self.recover(last_error)
+            .context("ROBOT HAT RECOVERY FAILED")?;
+        transaction(self.bus.as_mut()).context("ROBOT HAT UNRESPONSIVE AFTER RESET")
+    }
+
+    pub fn send(&mut self, command: Command) -> Result<()> {
+        self.transact(|bus| command.send(bus))?;
+        self.shadow.record(command);
+
+        Ok(())
+    }
+
+    fn recover(&mut self, cause: String) -> Result<()> {
+        if let Some(reset) = self.reset.as_mut() {
+            reset().context("MCU RESET FAILED")?;
+        }
+        mcu_init(self.bus.as_mut())?;
+
+        let commands = self.shadow.commands();
+        for command in &commands {
+            command.send(self.bus.as_mut())?;
+        }
+        self.report(RecoveryKind::Reset, 0, commands.len(), cause);
+
+        Ok(())
+    }
+
+    fn report(&mut self, kind: RecoveryKind, attempt: u32, replayed: usize, error: String) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(RecoveryEvent {
+            kind,
+            attempt,
+            replayed,
+            error,
+        });
+    }
+}
+
+// One handle per Robot HAT: the bus is opened and initialised once, and
+// every PWM channel handle shares it through this mutex
+#[pyclass]
+#[derive(Clone)]
+pub struct RobotHat {
+    inner: Arc<Mutex<HatBus>>,
+}
+
+impl RobotHat {
+    pub fn with_bus(bus: Box<dyn I2cBus>) -> Result<Self> {
+        RobotHat::with_parts(bus, None)
+    }
+
+    // `reset` pulses the MCU reset line, used when retries alone do not bring the bus back
+    pub fn with_reset(
+        bus: Box<dyn I2cBus>,
+        reset: impl FnMut() -> Result<()> + Send + 'static,
+    ) -> Result<Self> {
+        RobotHat::with_parts(bus, Some(Box::new(reset)))
+    }
+
+    fn with_parts(mut bus: Box<dyn I2cBus>, reset: Option<ResetFn>) -> Result<Self> {
+        mcu_init(bus.as_mut()).context("ROBOT HAT INIT FAILED")?;
+        let inner = HatBus {
+            bus,
+            shadow: Shadow::default(),
+            retry: RetryPolicy::default(),
+            reset,
+            events: VecDeque::new(),
+        };
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+
+    // Process wide handle, opened on first use
+    pub fn shared() -> Result<Self> {
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(hat) = shared.as_ref() {
+            return Ok(hat.clone());
+        }
+
+        let bus = open_bus().context("ROBOT HAT I2C OPEN FAILED")?;
+        let hat = RobotHat::with_reset(Box::new(bus), || Ok(board::current()?.reset_mcu()?))?;
+        *shared = Some(hat.clone());
+
+        Ok(hat)
+    }
+
+    pub fn ptr_eq(&self, other: &RobotHat) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    // Exclusive bus access until the guard drops, so multi-word transactions are not interleaved
+    pub fn lock(&self) -> MutexGuard<'_, HatBus> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[pymethods]
+impl RobotHat {
+    #[new]
+    pub fn new() -> Result<Self, PyError> {
+        Ok(RobotHat::shared()?)
+    }
+
+    // Re-send the MCU init bytes (e.g. after reset_mcu)
+    pub fn init(&self) -> Result<(), PyError> {
+        let mut hat = self.lock();
+        hat.transact(|bus| mcu_init(bus))
+            .context("ROBOT HAT INIT FAILED")?;
+        hat.shadow.clear();
+
+        Ok(())
+    }
+
+    pub fn pwm(&self, channel: u8) -> Result<PWM, PyError> {
+        Ok(PWM::with_hat(Channel::new(channel)?, self.clone())?)
+    }
+
+    pub fn adc(&self) -> Adc {
+        Adc::with_hat(self.clone())
+    }
+
+    // Native replacement for `i2cdetect -y 1`, defaults to ProbeMode.Auto
+    pub fn scan(&self, mode: Option<ProbeMode>) -> Result<Vec<I2cDevice>, PyError> {
+        // no retries or recovery: a diagnostic must never reset the MCU under a moving car
+        let mut hat = self.lock();
+        Ok(probe_bus(
+            hat.bus.as_mut(),
+            mode.unwrap_or(ProbeMode::Auto),
+        )?)
+    }
+
+    #[getter]
+    pub fn retry(&self) -> RetryPolicy {
+        self.lock().retry
+    }
+
+    #[setter]
+    pub fn set_retry(&self, retry: RetryPolicy) {
+        self.lock().retry = retry;
+    }
+
+    // Retries and resets since the last call, oldest first
+    pub fn take_events(&self) -> Vec<RecoveryEvent> {
+        self.lock().events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::BoardProfile,
+        error::Error,
+        mock::{MockBus, MockPin},
+    };
+
+    use rppal::gpio::Level;
+
+    fn mock_hat() -> (RobotHat, MockBus, MockPin) {
+        let mock = MockBus::new();
+        let pin = MockPin::new();
+        let mut rst = pin.clone();
+        let hat = RobotHat::with_reset(Box::new(mock.clone()), move || {
+            BoardProfile::reset_with(&mut rst);
+            Ok(())
+        })
+        .unwrap();
+        hat.set_retry(RetryPolicy::new(3, 0));
+        (hat, mock, pin)
+    }
+
+    #[test]
+    fn transient_errors_are_retried() {
+        let (hat, mock, pin) = mock_hat();
+        let mut pwm = hat.pwm(4).unwrap();
+        mock.clear();
+
+        mock.fail_next(2);
+        pwm.pulse_width(1000).unwrap();
+
+        assert_eq!(mock.last_word_write().unwrap().mcu_value(), 1000);
+        assert!(pin.levels().is_empty());
+        let events = hat.take_events();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.kind == RecoveryKind::Retry));
+        assert!(events[0].error.contains("121"));
+    }
+
+    #[test]
+    fn unresponsive_mcu_is_reset_and_replayed() {
+        let (hat, mock, pin) = mock_hat();
+        let mut steering = hat.pwm(2).unwrap();
+        let mut motor = hat.pwm(12).unwrap();
+        steering.pulse_width(4500).unwrap();
+        motor.pulse_width(30000).unwrap();
+        mock.clear();
+
+        mock.fail_next(3);
+        motor.pulse_width(20000).unwrap();
+
+        assert_eq!(pin.levels(), vec![Level::Low, Level::High]);
+        assert_eq!(mock.sent_bytes().len(), 3); // init bytes re-sent
+        let writes: Vec<(u8, u16)> = mock
+            .word_writes()
+            .iter()
+            .map(|w| (w.register, w.mcu_value()))
+            .collect();
+        // timers 0 and 3 first, then both pulse widths, then the retried write
+        assert_eq!(
+            writes,
+            vec![
+                (0x40, 23),
+                (0x43, 23),
+                (0x44, 59999),
+                (0x47, 59999),
+                (0x22, 4500),
+                (0x2C, 30000),
+                (0x2C, 20000),
+            ]
+        );
+
+        let reset = hat.take_events().pop().unwrap();
+        assert_eq!(reset.kind, RecoveryKind::Reset);
+        assert_eq!(reset.replayed, 6);
+    }
+
+    #[test]
+    fn persistent_failure_is_returned() {
+        let (hat, mock, _) = mock_hat();
+        let mut pwm = hat.pwm(0).unwrap();
+
+        mock.fail_next(usize::MAX);
+        let error = pwm.pulse_width(1000).unwrap_err();
+        assert!(matches!(error.kind(), Some(Error::Bus(_))));
+        assert!(hat.take_events().len() >= 3);
+    }
+}
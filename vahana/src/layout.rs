@@ -0,0 +1,249 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{fs, path::Path, sync::Mutex};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, PyError},
+    pins::PinMap,
+};
+
+// Read by layout_init() when no path is given
+pub const LAYOUT_PATH: &str = "hardware.toml";
+
+static LAYOUT: Mutex<Option<HardwareLayout>> = Mutex::new(None);
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MotorLayout {
+    #[pyo3(get, set)]
+    pub left_pwm: String,
+    #[pyo3(get, set)]
+    pub left_dir: String,
+    #[pyo3(get, set)]
+    pub right_pwm: String,
+    #[pyo3(get, set)]
+    pub right_dir: String,
+}
+
+impl Default for MotorLayout {
+    fn default() -> Self {
+        Self {
+            left_pwm: "P12".into(),
+            left_dir: "D4".into(),
+            right_pwm: "P13".into(),
+            right_dir: "D5".into(),
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServoLayout {
+    #[pyo3(get, set)]
+    pub camera_pan: String,
+    #[pyo3(get, set)]
+    pub camera_tilt: String,
+    #[pyo3(get, set)]
+    pub steering: String,
+}
+
+impl Default for ServoLayout {
+    fn default() -> Self {
+        Self {
+            camera_pan: "P0".into(),
+            camera_tilt: "P1".into(),
+            steering: "P2".into(),
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorLayout {
+    #[pyo3(get, set)]
+    pub ultrasonic_trig: String,
+    #[pyo3(get, set)]
+    pub ultrasonic_echo: String,
+    #[pyo3(get, set)]
+    pub grayscale: [String; 3], // left, middle, right
+    #[pyo3(get, set)]
+    pub battery: String,
+}
+
+impl Default for SensorLayout {
+    fn default() -> Self {
+        Self {
+            ultrasonic_trig: "D2".into(),
+            ultrasonic_echo: "D3".into(),
+            grayscale: ["A0".into(), "A1".into(), "A2".into()],
+            battery: "A4".into(),
+        }
+    }
+}
+
+// Where everything is wired, by robot-hat pin name. Missing keys keep the
+// PiCar-X defaults, e.g. a file with only `[motors] left_dir = "D6"` is valid
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HardwareLayout {
+    #[pyo3(get, set)]
+    pub board: Option<String>, // "v1" / "v2", detected from BOARD_TYPE when unset
+    #[pyo3(get, set)]
+    pub motors: MotorLayout,
+    #[pyo3(get, set)]
+    pub servos: ServoLayout,
+    #[pyo3(get, set)]
+    pub sensors: SensorLayout,
+}
+
+#[pymethods]
+impl HardwareLayout {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[staticmethod]
+    pub fn load(path: &str) -> Result<Self, PyError> {
+        Ok(read_layout(Path::new(path))?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), PyError> {
+        let text = toml::to_string(self).context("SERIALISING HARDWARE LAYOUT")?;
+        fs::write(path, text)
+            .map_err(Error::from)
+            .with_context(|| format!("WRITING HARDWARE LAYOUT {path}"))?;
+
+        Ok(())
+    }
+
+    // Resolves every name against `pins`, so a typo fails at start up and not on first use
+    pub fn validate(&self, pins: &PinMap) -> Result<(), PyError> {
+        for pwm in [
+            &self.motors.left_pwm,
+            &self.motors.right_pwm,
+            &self.servos.camera_pan,
+            &self.servos.camera_tilt,
+            &self.servos.steering,
+        ] {
+            pins.pwm(pwm).map_err(invalid_layout)?;
+        }
+        for gpio in [
+            &self.motors.left_dir,
+            &self.motors.right_dir,
+            &self.sensors.ultrasonic_trig,
+            &self.sensors.ultrasonic_echo,
+        ] {
+            pins.gpio(gpio).map_err(invalid_layout)?;
+        }
+        for analog in self.sensors.grayscale.iter().chain([&self.sensors.battery]) {
+            pins.analog(analog).map_err(invalid_layout)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid_layout(error: anyhow::Error) -> Error {
+    Error::Config(format!("HARDWARE LAYOUT: {error:#}"))
+}
+
+fn read_layout(path: &Path) -> Result<HardwareLayout> {
+    let text = fs::read_to_string(path)
+        .map_err(Error::from)
+        .with_context(|| format!("READING HARDWARE LAYOUT {}", path.display()))?;
+    toml::from_str(&text)
+        .map_err(Error::from)
+        .with_context(|| format!("PARSING HARDWARE LAYOUT {}", path.display()))
+}
+
+// Process wide layout: the last one installed, otherwise the defaults
+pub fn current() -> HardwareLayout {
+    LAYOUT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+pub fn set_current(layout: Option<HardwareLayout>) {
+    *LAYOUT.lock().unwrap_or_else(|e| e.into_inner()) = layout;
+}
+
+// Loads `path` (if it exists) and installs it, including its board override.
+// An explicit `board` ("v1" / "v2") wins over the file and skips detection
+pub fn load(path: impl AsRef<Path>, board: Option<&str>) -> Result<HardwareLayout> {
+    let path = path.as_ref();
+    let layout = if path.exists() {
+        read_layout(path)?
+    } else {
+        HardwareLayout::default()
+    };
+
+    let board = crate::board::select(board.or(layout.board.as_deref()))?;
+    layout.validate(&board.pins)?;
+    set_current(Some(layout.clone()));
+
+    Ok(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pins::PINS_V1;
+
+    #[test]
+    fn partial_file_keeps_defaults() {
+        let layout: HardwareLayout = toml::from_str(
+            r#"
+            board = "v2"
+
+            [motors]
+            left_dir = "D6"
+
+            [sensors]
+            grayscale = ["A3", "A2", "A1"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(layout.board.as_deref(), Some("v2"));
+        assert_eq!(layout.motors.left_dir, "D6");
+        assert_eq!(layout.motors.right_dir, "D5");
+        assert_eq!(layout.servos, ServoLayout::default());
+        assert_eq!(layout.sensors.grayscale, ["A3", "A2", "A1"]);
+        assert_eq!(layout.sensors.ultrasonic_trig, "D2");
+    }
+
+    #[test]
+    fn defaults_match_picar_x_wiring() {
+        let pins = PinMap::new(&PINS_V1);
+        let layout = HardwareLayout::default();
+        layout.validate(&pins).unwrap();
+
+        assert_eq!(pins.gpio(&layout.sensors.ultrasonic_trig).unwrap(), 27);
+        assert_eq!(pins.gpio(&layout.sensors.ultrasonic_echo).unwrap(), 22);
+        assert_eq!(pins.gpio(&layout.motors.left_dir).unwrap(), 23);
+        assert_eq!(pins.gpio(&layout.motors.right_dir).unwrap(), 24);
+        assert_eq!(pins.pwm(&layout.servos.steering).unwrap().index(), 2);
+    }
+
+    #[test]
+    fn bad_names_and_keys_are_rejected() {
+        let pins = PinMap::new(&PINS_V1);
+        let mut layout = HardwareLayout::default();
+        layout.motors.left_pwm = "D4".into();
+
+        assert!(layout.validate(&pins).is_err());
+        assert!(toml::from_str::<HardwareLayout>("[motors]\nleft_pmw = \"P12\"").is_err());
+    }
+}
@@ -2,21 +2,41 @@
 
 use pyo3::prelude::*;
 
+pub mod adc;
 pub mod axel;
+pub mod battery;
+pub mod board;
+pub mod control;
 pub mod drive;
+pub use drishti::error;
+pub mod hal;
+pub mod hat;
+pub mod layout;
+pub mod mock;
+pub mod motion;
 pub mod neck;
+pub mod odometry;
+pub mod pins;
+pub mod protocol;
+pub mod scan;
+pub mod shutdown;
+pub mod timing;
+pub mod vehicle;
+pub mod watchdog;
 
 use anyhow::{Context, Result};
-use std::{process::Command, thread::sleep, time::Duration};
+use std::{thread::sleep, time::Duration};
 
 use rppal::i2c::I2c;
 
+use error::{Error, PyError};
+use hal::{I2cBus, LinuxI2c};
+use hat::RobotHat;
+use protocol::{Channel, Command};
+use timing::PwmTiming;
+
 const I2C_BUS: u8 = 1;
-const REG_PW: u8 = 0x20; // REG_CHN
-const REG_PSC: u8 = 0x40; // REG_PSC
-const REG_PER: u8 = 0x44; // REG_ARR
 const SLAVE_ADDR: u16 = 0x14;
-// const CLOCK: u32 = 72_000_000;
 
 pub fn map_range(from_range: (i32, i32), to_range: (i32, i32), s: i32) -> i32 {
     to_range.0 + (s - from_range.0) * (to_range.1 - to_range.0) / (from_range.1 - from_range.0)
@@ -24,166 +44,217 @@ pub fn map_range(from_range: (i32, i32), to_range: (i32, i32), s: i32) -> i32 {
 
 #[pyclass]
 pub struct MyI2c {
-    pub i2c: I2c,
+    pub i2c: Box<dyn I2cBus>,
 }
 
-#[pymethods]
-impl MyI2c {
-    #[new]
-    pub fn new() -> Result<MyI2c> {
-        let mut i2c = I2c::with_bus(I2C_BUS).context("Constructing new I2C failed")?;
-        // wait after I2C init to avopid 121 IO error
-        sleep(Duration::from_secs(1));
-
-        i2c.set_slave_address(SLAVE_ADDR)
-            .context("Setting SLAVE addr failed")?;
-        i2c.smbus_send_byte(0x2C)
-            .context("Sending byte 0x2c failed")?;
-        i2c.smbus_send_byte(0x00)
-            .context("Sending byte 0x00 failed")?;
-        i2c.smbus_send_byte(0x00)
-            .context("Sending byte 0x00 failed")?;
+pub fn open_bus() -> Result<LinuxI2c> {
+    let i2c = I2c::with_bus(I2C_BUS)
+        .map_err(Error::from)
+        .context("Constructing new I2C failed")?;
+    // wait after I2C init to avopid 121 IO error
+    sleep(Duration::from_secs(1));
 
-        Ok(MyI2c { i2c })
-    }
+    Ok(LinuxI2c::new(i2c))
 }
 
-#[pyfunction]
-pub fn init_i2c() -> Result<MyI2c> {
-    MyI2c::new()
+pub fn mcu_init(i2c: &mut dyn I2cBus) -> Result<()> {
+    i2c.set_slave_address(SLAVE_ADDR)
+        .context("Setting SLAVE addr failed")?;
+    i2c.smbus_send_byte(0x2C)
+        .context("Sending byte 0x2c failed")?;
+    i2c.smbus_send_byte(0x00)
+        .context("Sending byte 0x00 failed")?;
+    i2c.smbus_send_byte(0x00)
+        .context("Sending byte 0x00 failed")?;
+
+    Ok(())
 }
 
-fn run_command(cmd: &str) -> Result<(i32, String), Box<dyn std::error::Error>> {
-    let output = Command::new("sh") // You can use "sh" to execute shell commands
-        .arg("-c") // Use the -c flag to run the provided command
-        .arg(cmd) // The command you want to run
-        .output()?;
-
-    let status = output.status.code().unwrap_or(-1);
-    let result = String::from_utf8_lossy(&output.stdout).to_string();
+impl MyI2c {
+    pub fn with_bus(mut i2c: Box<dyn I2cBus>) -> Result<MyI2c> {
+        mcu_init(i2c.as_mut())?;
 
-    Ok((status, result))
+        Ok(MyI2c { i2c })
+    }
 }
 
-pub fn scan_i2c(i2c: I2c) -> Vec<u16> {
-    let cmd = format!("i2cdetect -y {}", i2c.bus());
-    let output = match run_command(&cmd) {
-        Ok((status, result)) => {
-            println!("Exit Status: {}", status);
-            println!("Command Output:\n{}", result);
-            result
-        }
-        Err(err) => err.to_string(),
-    };
-
-    let mut addresses = vec![];
-
-    for line in output.lines().skip(1) {
-        let tmp_addresses = line.split(':').nth(1).unwrap_or("").trim();
-        for address in tmp_addresses.split_whitespace() {
-            if address != "--" {
-                if let Ok(address) = u16::from_str_radix(address, 16) {
-                    addresses.push(address);
-                }
-            }
-        }
+#[pymethods]
+impl MyI2c {
+    #[new]
+    pub fn new() -> Result<MyI2c, PyError> {
+        let i2c = open_bus()?;
+        Ok(MyI2c::with_bus(Box::new(i2c))?)
     }
+}
 
-    addresses
+#[pyfunction]
+pub fn init_i2c() -> Result<MyI2c, PyError> {
+    MyI2c::new()
 }
 
 #[pyclass]
 pub struct PWM {
-    channel: u8,
-    period: Vec<u16>,
-    bus: I2c,
+    channel: Channel,
+    hat: RobotHat,
+}
+
+impl PWM {
+    fn with_hat(channel: Channel, hat: RobotHat) -> Result<Self> {
+        let mut pwm = Self { channel, hat };
+
+        pwm.freq(50.0).context("PWM FREQ INIT FAILED")?;
+
+        Ok(pwm)
+    }
 }
 
 #[pymethods]
 impl PWM {
     #[new]
-    pub fn new(channel: u8) -> Result<Self> {
-        let bus = init_i2c().context("PWM I2C INIT FAILED")?;
-        let bus = bus.i2c;
-        let period = vec![0, 0, 0, 0];
-        let mut pwm = Self {
-            channel,
-            period,
-            bus,
-        };
-
-        pwm.freq(50).context("PWM FREQ INIT FAILED")?;
+    pub fn new(channel: u8) -> Result<Self, PyError> {
+        let hat = RobotHat::shared().context("PWM I2C INIT FAILED")?;
+        hat.pwm(channel)
+    }
 
-        Ok(pwm)
+    #[getter]
+    pub fn channel(&self) -> u8 {
+        self.channel.index()
     }
 
-    pub fn freq(&mut self, freq: u16) -> Result<()> {
-        /*  Buggy code: For now, we hardcode the values
-                let mut result_psc = Vec::with_capacity(12); // Create a vector for prescaler
-                let mut result_per = Vec::with_capacity(12); // Create a vector for period
-                let mut result_acy = Vec::with_capacity(12); // Create a vector for accuracy
+    // Solves prescaler/period for `freq` Hz and writes them to the channel's timer
+    pub fn freq(&mut self, freq: f64) -> Result<PwmTiming, PyError> {
+        let timing = PwmTiming::solve(freq).context("PWM FREQ SOLVE FAILED")?;
 
-                let st = ((CLOCK as f32 / freq as f32).sqrt() as u16) - 5;
+        self.prescaler(timing.prescaler)
+            .context("PWM PRESCALER INIT FAILED")?;
+        self.period(timing.period)
+            .context("PWM PERIOD INIT FAILED")?;
 
-                for psc in st..st + 10 {
-                    let per = (CLOCK / (freq * psc) as u32) as u16;
-                    result_psc.push(psc);
-                    result_per.push(per);
-                    result_acy.push(f32::abs(freq as f32 - CLOCK as f32 / (psc * per) as f32));
-                }
+        Ok(timing)
+    }
 
-                let i = result_acy
-                    .iter()
-                    .position(|&x| x == result_acy.iter().cloned().fold(f32::INFINITY, f32::min))
-                    .unwrap();
-                let psc = result_psc[i];
-                let per = result_per[i];
-        */
-        let psc: u16 = freq * 24; // 1200
-        let per: u16 = freq * 24; // 1200
+    pub fn prescaler(&mut self, prescaler: u16) -> Result<(), PyError> {
+        let command = Command::prescaler(self.channel.timer(), prescaler)?;
+        Ok(self
+            .hat
+            .lock()
+            .send(command)
+            .context("PWM PRESCALER SEND FAILED")?)
+    }
 
-        self.prescaler(psc).context("PWM PRESCALER INIT FAILED")?;
-        self.period(per).context("PWM PERIOD INIT FAILED")?;
+    pub fn period(&mut self, per: u16) -> Result<(), PyError> {
+        let command = Command::period(self.channel.timer(), per)?;
+        Ok(self
+            .hat
+            .lock()
+            .send(command)
+            .context("PWM PERIOD SEND FAILED")?)
+    }
 
-        Ok(())
+    pub fn pulse_width(&mut self, pw: u16) -> Result<(), PyError> {
+        Ok(self
+            .hat
+            .lock()
+            .send(Command::pulse_width(self.channel, pw))
+            .context("PWM PULSE WIDTH SEND FAILED")?)
     }
 
-    pub fn prescaler(&mut self, prescaler: u16) -> Result<()> {
-        let timer = self.channel / 4_u8;
-        let reg = REG_PSC + timer;
-        self.bus
-            .smbus_write_word(reg, (prescaler - 1).swap_bytes())
-            .context("PWM PRESCALER SEND FAILED")?;
+    pub fn pulse_width_percent(&mut self, pulse_width_percent: u32) -> Result<(), PyError> {
+        let timer = self.channel.timer();
+        let period = self.hat.lock().shadow.period(timer);
+        let pulse_width = ((period as u32 * pulse_width_percent) / 100) as u16;
+        self.pulse_width(pulse_width)?;
 
         Ok(())
     }
+}
 
-    pub fn period(&mut self, per: u16) -> Result<()> {
-        let timer = self.channel / 4_u8;
-        let reg = REG_PER + timer;
-        self.period[timer as usize] = per - 1;
-        self.bus
-            .smbus_write_word(reg, self.period[timer as usize].swap_bytes())
-            .context("PWM PERIOD SEND FAILED")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockBus;
+
+    fn mock_pwm(channel: u8) -> (PWM, MockBus) {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let pwm = hat.pwm(channel).unwrap();
+        mock.clear();
+        (pwm, mock)
+    }
 
-        Ok(())
+    #[test]
+    fn init_sends_mcu_wakeup_bytes() {
+        let mock = MockBus::new();
+        MyI2c::with_bus(Box::new(mock.clone())).unwrap();
+
+        assert_eq!(mock.address(), SLAVE_ADDR);
+        assert_eq!(
+            mock.sent_bytes(),
+            vec![(SLAVE_ADDR, 0x2C), (SLAVE_ADDR, 0x00), (SLAVE_ADDR, 0x00)]
+        );
+    }
+
+    #[test]
+    fn prescaler_targets_channel_timer() {
+        for (channel, reg) in [(0, 0x40), (3, 0x40), (4, 0x41), (11, 0x42), (13, 0x43)] {
+            let (mut pwm, mock) = mock_pwm(channel);
+            pwm.prescaler(351).unwrap();
+
+            let write = mock.last_word_write().unwrap();
+            assert_eq!(write.address, SLAVE_ADDR);
+            assert_eq!(write.register, reg);
+            assert_eq!(write.mcu_value(), 350);
+            assert_eq!(write.bytes(), [0x01, 0x5E]);
+        }
     }
 
-    pub fn pulse_width(&mut self, pw: u16) -> Result<()> {
-        let reg = REG_PW + self.channel;
-        self.bus
-            .smbus_write_word(reg, pw.swap_bytes())
-            .context("PWM PULSE WIDTH SEND FAILED")?;
+    #[test]
+    fn period_targets_channel_timer() {
+        let (mut pwm, mock) = mock_pwm(5);
+        pwm.period(4095).unwrap();
 
-        Ok(())
+        let write = mock.last_word_write().unwrap();
+        assert_eq!(write.register, 0x45);
+        assert_eq!(write.mcu_value(), 4094);
+        assert_eq!(write.bytes(), [0x0F, 0xFE]);
     }
 
-    // Buggy code ? !!
-    pub fn pulse_width_percent(&mut self, pulse_width_percent: u32) -> Result<()> {
-        let timer = self.channel / 4_u8;
-        let pulse_width = ((self.period[timer as usize] as u32 * pulse_width_percent) / 100) as u16;
-        self.pulse_width(pulse_width)?;
+    #[test]
+    fn pulse_width_percent_scales_timer_period() {
+        let (mut pwm, mock) = mock_pwm(12);
+        pwm.period(1200).unwrap();
 
-        Ok(())
+        for (percent, expected) in [(0, 0), (50, 599), (100, 1199)] {
+            pwm.pulse_width_percent(percent).unwrap();
+
+            let write = mock.last_word_write().unwrap();
+            assert_eq!(write.register, 0x2C);
+            assert_eq!(write.mcu_value(), expected);
+        }
+    }
+
+    #[test]
+    fn channels_share_bus_and_timer_period() {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let mut left = hat.pwm(12).unwrap();
+        let mut right = hat.pwm(13).unwrap();
+        assert_eq!(mock.sent_bytes().len(), 3); // MCU initialised once
+
+        left.period(1000).unwrap();
+        right.pulse_width_percent(50).unwrap();
+
+        let write = mock.last_word_write().unwrap();
+        assert_eq!(write.register, 0x2D);
+        assert_eq!(write.mcu_value(), 499);
+    }
+
+    #[test]
+    fn out_of_range_channel_is_rejected() {
+        let hat = RobotHat::with_bus(Box::new(MockBus::new())).unwrap();
+
+        assert!(hat.pwm(13).is_ok());
+        let error = hat.pwm(14).err().unwrap();
+        assert!(matches!(error.kind(), Some(Error::InvalidArgument(_))));
     }
 }
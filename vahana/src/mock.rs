@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, bail, Result};
+use rppal::gpio::Level;
+
+use crate::{
+    error::Error,
+    hal::{DigitalInput, DigitalOutput, I2cBus},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian, // smbus_write_word
+    BigEndian,    // smbus_write_word_swapped
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordWrite {
+    pub address: u16,
+    pub register: u8,
+    pub value: u16,
+    pub order: ByteOrder,
+}
+
+impl WordWrite {
+    // Bytes in the order they go out on the wire
+    pub fn bytes(&self) -> [u8; 2] {
+        match self.order {
+            ByteOrder::LittleEndian => self.value.to_le_bytes(),
+            ByteOrder::BigEndian => self.value.to_be_bytes(),
+        }
+    }
+
+    // Value as decoded by the Robot HAT MCU (high byte first)
+    pub fn mcu_value(&self) -> u16 {
+        u16::from_be_bytes(self.bytes())
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    address: u16,
+    devices: HashSet<u16>, // addresses that acknowledge probes
+    sent_bytes: Vec<(u16, u8)>,
+    word_writes: Vec<WordWrite>,
+    read_words: HashMap<(u16, u8), VecDeque<u16>>,
+    responses: HashMap<(u16, u8), Vec<u8>>, // bytes to receive after a word write to (address, register)
+    pending: VecDeque<u8>,
+    failures: usize,           // transactions left to fail with a remote I/O error
+    unreachable: HashSet<u16>, // addresses set_slave_address rejects
+}
+
+// In-memory I2C bus, clones share the same recorded state
+#[derive(Clone, Default)]
+pub struct MockBus {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn address(&self) -> u16 {
+        self.state().address
+    }
+
+    pub fn add_device(&self, address: u16) {
+        self.state().devices.insert(address);
+    }
+
+    // The next `count` transactions fail, like the MCU answering errno 121
+    pub fn fail_next(&self, count: usize) {
+        self.state().failures = count;
+    }
+
+    // Selecting `address` fails from now on, as if the ioctl was refused
+    pub fn reject_address(&self, address: u16) {
+        self.state().unreachable.insert(address);
+    }
+
+    fn fault(&self) -> Result<()> {
+        let mut state = self.state();
+        if state.failures > 0 {
+            state.failures -= 1;
+            let remote_io = io::Error::from_raw_os_error(121); // EREMOTEIO
+            bail!(Error::Bus(rppal::i2c::Error::Io(remote_io)));
+        }
+
+        Ok(())
+    }
+
+    fn probe(&self) -> Result<()> {
+        let state = self.state();
+        if !state.devices.contains(&state.address) {
+            bail!(Error::DeviceNotFound(state.address));
+        }
+
+        Ok(())
+    }
+
+    pub fn sent_bytes(&self) -> Vec<(u16, u8)> {
+        self.state().sent_bytes.clone()
+    }
+
+    pub fn word_writes(&self) -> Vec<WordWrite> {
+        self.state().word_writes.clone()
+    }
+
+    pub fn last_word_write(&self) -> Option<WordWrite> {
+        self.state().word_writes.last().copied()
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.sent_bytes.clear();
+        state.word_writes.clear();
+    }
+
+    // Queue a word to be answered by the next smbus_read_word on (address, register)
+    pub fn queue_read_word(&self, address: u16, register: u8, value: u16) {
+        self.state()
+            .read_words
+            .entry((address, register))
+            .or_default()
+            .push_back(value);
+    }
+
+    // Canned reply: a word write to (address, register) loads `bytes` for the following receive_byte calls
+    pub fn set_response(&self, address: u16, register: u8, bytes: &[u8]) {
+        self.state()
+            .responses
+            .insert((address, register), bytes.to_vec());
+    }
+
+    fn record_word(&self, register: u8, value: u16, order: ByteOrder) {
+        let mut state = self.state();
+        let address = state.address;
+        state.word_writes.push(WordWrite {
+            address,
+            register,
+            value,
+            order,
+        });
+        if let Some(bytes) = state.responses.get(&(address, register)).cloned() {
+            state.pending = bytes.into();
+        }
+    }
+}
+
+impl I2cBus for MockBus {
+    fn set_slave_address(&mut self, address: u16) -> Result<()> {
+        let mut state = self.state();
+        if state.unreachable.contains(&address) {
+            bail!("MOCK: ADDRESS 0x{address:02x} REJECTED");
+        }
+        state.address = address;
+        Ok(())
+    }
+
+    fn smbus_quick_command(&mut self, _command: bool) -> Result<()> {
+        self.fault()?;
+        self.probe()
+    }
+
+    fn smbus_receive_byte(&mut self) -> Result<u8> {
+        self.fault()?;
+        if let Some(byte) = self.state().pending.pop_front() {
+            return Ok(byte);
+        }
+
+        self.probe().map(|_| 0)
+    }
+
+    fn smbus_send_byte(&mut self, value: u8) -> Result<()> {
+        self.fault()?;
+        let mut state = self.state();
+        let address = state.address;
+        state.sent_bytes.push((address, value));
+        Ok(())
+    }
+
+    fn smbus_write_word(&mut self, command: u8, value: u16) -> Result<()> {
+        self.fault()?;
+        self.record_word(command, value, ByteOrder::LittleEndian);
+        Ok(())
+    }
+
+    fn smbus_write_word_swapped(&mut self, command: u8, value: u16) -> Result<()> {
+        self.fault()?;
+        self.record_word(command, value, ByteOrder::BigEndian);
+        Ok(())
+    }
+
+    fn smbus_read_word(&mut self, command: u8) -> Result<u16> {
+        self.fault()?;
+        let mut state = self.state();
+        let address = state.address;
+        state
+            .read_words
+            .get_mut(&(address, command))
+            .and_then(VecDeque::pop_front)
+            .ok_or_else(|| anyhow!("MOCK: NO WORD QUEUED FOR 0x{address:02x}/0x{command:02x}"))
+    }
+}
+
+// In-memory output pin recording every level written to it
+#[derive(Clone, Default)]
+pub struct MockPin {
+    levels: Arc<Mutex<Vec<Level>>>,
+}
+
+impl MockPin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Reads back `level` until something is written
+    pub fn with_level(level: Level) -> Self {
+        Self {
+            levels: Arc::new(Mutex::new(vec![level])),
+        }
+    }
+
+    pub fn levels(&self) -> Vec<Level> {
+        self.levels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn level(&self) -> Option<Level> {
+        self.levels().last().copied()
+    }
+}
+
+impl DigitalOutput for MockPin {
+    fn write(&mut self, level: Level) {
+        self.levels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(level);
+    }
+}
+
+impl DigitalInput for MockPin {
+    fn read(&self) -> Level {
+        self.level().unwrap_or(Level::Low)
+    }
+}
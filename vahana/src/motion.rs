@@ -0,0 +1,583 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    drive::Servo,
+    error::{Error, PyError},
+    timing::seconds,
+    vehicle::Vehicle,
+};
+
+const MOTION_TICK: Duration = Duration::from_millis(20);
+const PROGRESS_STEP: f64 = 0.25; // a Progress event every quarter of a motion
+const MAX_EVENTS: usize = 64; // oldest events are dropped past this
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MotionKind {
+    Drive { speed: f64, angle: f64 }, // Ackermann, angle at the front wheels (deg)
+    Sweep { servo: String, from: i32, to: i32 },
+    Stop,
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Motion {
+    pub kind: MotionKind,
+    pub duration: Duration,
+}
+
+impl Motion {
+    pub fn new(kind: MotionKind, duration: Duration) -> Self {
+        Self { kind, duration }
+    }
+}
+
+#[pymethods]
+impl Motion {
+    #[staticmethod]
+    pub fn forward(speed: f64, duration_s: f64) -> Result<Self, PyError> {
+        Motion::turn(0.0, speed, duration_s)
+    }
+
+    #[staticmethod]
+    pub fn backward(speed: f64, duration_s: f64) -> Result<Self, PyError> {
+        Motion::turn(0.0, -speed, duration_s)
+    }
+
+    // Steering `angle` in degrees, positive to the right
+    #[staticmethod]
+    pub fn turn(angle: f64, speed: f64, duration_s: f64) -> Result<Self, PyError> {
+        let kind = MotionKind::Drive { speed, angle };
+        Ok(Motion::new(kind, seconds("MOTION DURATION", duration_s)?))
+    }
+
+    // Moves `servo` (a name given to the executor) linearly from `from` to `to`
+    #[staticmethod]
+    pub fn sweep(servo: String, from: i32, to: i32, duration_s: f64) -> Result<Self, PyError> {
+        let kind = MotionKind::Sweep { servo, from, to };
+        Ok(Motion::new(kind, seconds("MOTION DURATION", duration_s)?))
+    }
+
+    // Stops, then holds still for `duration_s`
+    #[staticmethod]
+    #[pyo3(signature = (duration_s = 0.0))]
+    pub fn stop(duration_s: f64) -> Result<Self, PyError> {
+        Ok(Motion::new(
+            MotionKind::Stop,
+            seconds("MOTION DURATION", duration_s)?,
+        ))
+    }
+
+    #[getter]
+    pub fn duration_s(&self) -> f64 {
+        self.duration.as_secs_f64()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionState {
+    Started,
+    Progress,
+    Completed,
+    Cancelled, // by cancel(), replace() or the executor being dropped
+    Failed,    // the rest of the queue is dropped and the vehicle stopped
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionEvent {
+    #[pyo3(get)]
+    pub id: u64,
+    #[pyo3(get)]
+    pub state: MotionState,
+    #[pyo3(get)]
+    pub progress: f64, // 0.0 .. 1.0
+    #[pyo3(get)]
+    pub error: Option<String>,
+}
+
+#[pymethods]
+impl MotionEvent {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+struct Queue {
+    pending: VecDeque<(u64, Motion)>,
+    current: Option<(u64, f64)>, // id and progress of the running motion
+    interrupt: bool,
+    running: bool,
+    next_id: u64,
+    events: VecDeque<MotionEvent>,
+    subscribers: Vec<mpsc::Sender<MotionEvent>>,
+}
+
+impl Queue {
+    fn emit(&mut self, id: u64, state: MotionState, progress: f64, error: Option<String>) {
+        let event = MotionEvent {
+            id,
+            state,
+            progress,
+            error,
+        };
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    fn busy(&self) -> bool {
+        self.current.is_some() || !self.pending.is_empty()
+    }
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    wake: Condvar, // queue changes, interrupts and finished motions
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Runs queued motions one after another on its own thread. A drive motion
+// that leaves the queue empty stops the vehicle
+#[pyclass]
+pub struct MotionExecutor {
+    shared: Arc<Shared>,
+}
+
+impl MotionExecutor {
+    pub fn with_vehicle(vehicle: Vehicle, servos: BTreeMap<String, Servo>) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                pending: VecDeque::new(),
+                current: None,
+                interrupt: false,
+                running: true,
+                next_id: 1,
+                events: VecDeque::new(),
+                subscribers: Vec::new(),
+            }),
+            wake: Condvar::new(),
+        });
+        let worker = Worker {
+            shared: shared.clone(),
+            vehicle,
+            servos,
+        };
+        thread::spawn(move || worker.run());
+
+        Self { shared }
+    }
+
+    // Every event from now on, e.g. for a Rust control loop
+    pub fn subscribe(&self) -> mpsc::Receiver<MotionEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.shared.lock().subscribers.push(sender);
+        receiver
+    }
+
+    fn enqueue(&self, motions: Vec<Motion>, replace: bool) -> Vec<u64> {
+        let mut queue = self.shared.lock();
+        if replace {
+            queue.pending.clear();
+            queue.interrupt = queue.current.is_some();
+        }
+        let ids = motions
+            .into_iter()
+            .map(|motion| {
+                let id = queue.next_id;
+                queue.next_id += 1;
+                queue.pending.push_back((id, motion));
+                id
+            })
+            .collect();
+        self.shared.wake.notify_all();
+
+        ids
+    }
+
+    // Blocks until the queue is empty, false on timeout
+    pub fn wait_idle(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut queue = self.shared.lock();
+        while queue.busy() {
+            let wait = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(wait) => wait,
+                    None => return false,
+                },
+                None => MOTION_TICK * 50,
+            };
+            queue = self
+                .shared
+                .wake
+                .wait_timeout(queue, wait)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        true
+    }
+}
+
+impl Drop for MotionExecutor {
+    fn drop(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.running = false;
+        queue.interrupt = true;
+        queue.pending.clear();
+        self.shared.wake.notify_all();
+    }
+}
+
+#[pymethods]
+impl MotionExecutor {
+    // `servos` names the servos sweep motions can move, e.g. {"pan": pan_servo}
+    #[new]
+    #[pyo3(signature = (vehicle, servos = BTreeMap::new()))]
+    pub fn new(vehicle: PyRef<'_, Vehicle>, servos: BTreeMap<String, Servo>) -> Self {
+        MotionExecutor::with_vehicle(vehicle.clone(), servos)
+    }
+
+    // Queues `motion` after the others and returns its id
+    pub fn push(&self, motion: Motion) -> u64 {
+        self.enqueue(vec![motion], false)[0]
+    }
+
+    pub fn extend(&self, motions: Vec<Motion>) -> Vec<u64> {
+        self.enqueue(motions, false)
+    }
+
+    // Drops the queue and cuts the running motion short, without stopping in between
+    pub fn replace(&self, motions: Vec<Motion>) -> Vec<u64> {
+        self.enqueue(motions, true)
+    }
+
+    // Drops the queue and cuts the running motion short; the vehicle stops
+    pub fn cancel(&self) {
+        self.enqueue(vec![], true);
+    }
+
+    #[getter]
+    pub fn busy(&self) -> bool {
+        self.shared.lock().busy()
+    }
+
+    // (id, progress) of the running motion
+    #[getter]
+    pub fn current(&self) -> Option<(u64, f64)> {
+        self.shared.lock().current
+    }
+
+    #[getter]
+    pub fn pending(&self) -> Vec<u64> {
+        self.shared
+            .lock()
+            .pending
+            .iter()
+            .map(|&(id, _)| id)
+            .collect()
+    }
+
+    // Events since the last call, oldest first
+    pub fn take_events(&self) -> Vec<MotionEvent> {
+        self.shared.lock().events.drain(..).collect()
+    }
+
+    // Replaces time.sleep(): returns once all motions are done, False on timeout
+    #[pyo3(signature = (timeout_s = None))]
+    pub fn wait(&self, py: Python<'_>, timeout_s: Option<f64>) -> Result<bool, PyError> {
+        let timeout = timeout_s
+            .map(|timeout_s| seconds("MOTION WAIT TIMEOUT", timeout_s))
+            .transpose()?;
+        Ok(py.allow_threads(|| self.wait_idle(timeout)))
+    }
+}
+
+struct Worker {
+    shared: Arc<Shared>,
+    vehicle: Vehicle,
+    servos: BTreeMap<String, Servo>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        while let Some((id, motion)) = self.next() {
+            self.shared.lock().emit(id, MotionState::Started, 0.0, None);
+            let result = self.execute(id, &motion);
+
+            let mut queue = self.shared.lock();
+            let progress = queue.current.map_or(0.0, |(_, progress)| progress);
+            let failed = match result {
+                Ok(true) => {
+                    queue.emit(id, MotionState::Completed, 1.0, None);
+                    false
+                }
+                Ok(false) => {
+                    queue.emit(id, MotionState::Cancelled, progress, None);
+                    false
+                }
+                Err(error) => {
+                    let error = format!("{error:#}");
+                    queue.emit(id, MotionState::Failed, progress, Some(error));
+                    queue.pending.clear();
+                    true
+                }
+            };
+            let idle = queue.pending.is_empty();
+            drop(queue);
+
+            // park outside the lock, then report idle so wait() sees a stopped car
+            if failed {
+                let _ = self.vehicle.emergency_stop();
+            } else if idle && matches!(motion.kind, MotionKind::Drive { .. }) {
+                let _ = self.vehicle.stop();
+            }
+            self.shared.lock().current = None;
+            self.shared.wake.notify_all();
+        }
+    }
+
+    // Next queued motion, None once the executor is dropped
+    fn next(&self) -> Option<(u64, Motion)> {
+        let mut queue = self.shared.lock();
+        loop {
+            if !queue.running {
+                return None;
+            }
+            if let Some((id, motion)) = queue.pending.pop_front() {
+                queue.current = Some((id, 0.0));
+                queue.interrupt = false;
+                return Some((id, motion));
+            }
+            queue = self
+                .shared
+                .wake
+                .wait(queue)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    // true when the motion ran to the end, false when interrupted
+    fn execute(&mut self, id: u64, motion: &Motion) -> Result<bool> {
+        match &motion.kind {
+            MotionKind::Drive { speed, angle } => {
+                self.vehicle
+                    .drive_angle(*speed, *angle)
+                    .context("MOTION DRIVE FAILED")?;
+                let motors = self.vehicle.motors();
+                // keeps an enabled watchdog quiet, the executor is the one in control
+                self.hold(id, motion.duration, |_| {
+                    motors.feed();
+                    Ok(())
+                })
+            }
+            MotionKind::Sweep { servo, from, to } => {
+                let servo = self.servos.get(servo).cloned().ok_or_else(|| {
+                    Error::invalid(format!("NO SERVO NAMED {servo:?} FOR THIS EXECUTOR"))
+                })?;
+                let span = (to - from) as f64;
+                self.hold(id, motion.duration, |fraction| {
+                    let angle = *from as f64 + span * fraction;
+                    Ok(servo.angle(angle.round() as i32)?)
+                })
+            }
+            MotionKind::Stop => {
+                self.vehicle.stop().context("MOTION STOP FAILED")?;
+                self.hold(id, motion.duration, |_| Ok(()))
+            }
+        }
+    }
+
+    // Calls `step` with the elapsed fraction every MOTION_TICK, on a fixed schedule from the start
+    fn hold(
+        &self,
+        id: u64,
+        duration: Duration,
+        mut step: impl FnMut(f64) -> Result<()>,
+    ) -> Result<bool> {
+        let start = Instant::now();
+        let end = start + duration;
+        let mut reported = 0.0;
+        for tick in 1.. {
+            let elapsed = Instant::now().saturating_duration_since(start);
+            let fraction = if duration.is_zero() {
+                1.0
+            } else {
+                (elapsed.as_secs_f64() / duration.as_secs_f64()).min(1.0)
+            };
+            step(fraction)?;
+
+            let mut queue = self.shared.lock();
+            queue.current = Some((id, fraction));
+            if fraction >= 1.0 {
+                return Ok(true);
+            }
+            if fraction - reported >= PROGRESS_STEP {
+                reported = fraction;
+                queue.emit(id, MotionState::Progress, fraction, None);
+            }
+
+            let deadline = (start + MOTION_TICK * tick).min(end);
+            loop {
+                if queue.interrupt || !queue.running {
+                    return Ok(false);
+                }
+                let Some(wait) = deadline.checked_duration_since(Instant::now()) else {
+                    break;
+                };
+                queue = self
+                    .shared
+                    .wake
+                    .wait_timeout(queue, wait)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        drive::{Motor, Motors, SERVO_FREQ},
+        hat::RobotHat,
+        mock::{MockBus, MockPin},
+        vehicle::VehicleGeometry,
+    };
+
+    fn mock_executor(mock: &MockBus) -> (MotionExecutor, Vehicle, Servo) {
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let left = Motor::with_parts(hat.pwm(12).unwrap(), Box::new(MockPin::new()), 50.0);
+        let right = Motor::with_parts(hat.pwm(13).unwrap(), Box::new(MockPin::new()), 50.0);
+        let motors = Motors::with_motors(left.unwrap(), right.unwrap());
+        let steering = Servo::with_pwm(hat.pwm(2).unwrap(), SERVO_FREQ).unwrap();
+        let vehicle = Vehicle::with_parts(motors, steering, VehicleGeometry::default()).unwrap();
+        let pan = Servo::with_pwm(hat.pwm(0).unwrap(), SERVO_FREQ).unwrap();
+        let servos = BTreeMap::from([("pan".to_string(), pan.clone())]);
+
+        (
+            MotionExecutor::with_vehicle(vehicle.clone(), servos),
+            vehicle,
+            pan,
+        )
+    }
+
+    fn states(events: &[MotionEvent], id: u64) -> Vec<MotionState> {
+        events
+            .iter()
+            .filter(|event| event.id == id && event.state != MotionState::Progress)
+            .map(|event| event.state)
+            .collect()
+    }
+
+    #[test]
+    fn queued_motions_run_in_order_then_stop() {
+        let mock = MockBus::new();
+        let (executor, vehicle, _) = mock_executor(&mock);
+        let events = executor.subscribe();
+
+        let started = Instant::now();
+        let ids = executor.extend(vec![
+            Motion::forward(40.0, 0.06).unwrap(),
+            Motion::turn(20.0, 30.0, 0.04).unwrap(),
+        ]);
+        assert!(executor.wait_idle(Some(Duration::from_secs(2))));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let events: Vec<MotionEvent> = events.try_iter().collect();
+        for &id in &ids {
+            assert_eq!(
+                states(&events, id),
+                vec![MotionState::Started, MotionState::Completed]
+            );
+        }
+        assert!(events.iter().any(|e| e.state == MotionState::Progress));
+        assert_eq!(vehicle.motors().target(), (0.0, 0.0));
+        assert_eq!(executor.take_events().len(), events.len());
+    }
+
+    #[test]
+    fn cancel_and_replace_interrupt_the_running_motion() {
+        let mock = MockBus::new();
+        let (executor, vehicle, _) = mock_executor(&mock);
+
+        let first = executor.push(Motion::forward(50.0, 5.0).unwrap());
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(vehicle.motors().target(), (50.0, 50.0));
+        let second = executor.replace(vec![Motion::backward(30.0, 5.0).unwrap()])[0];
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(executor.current().map(|(id, _)| id), Some(second));
+        assert_eq!(vehicle.motors().target(), (-30.0, -30.0));
+
+        executor.cancel();
+        assert!(executor.wait_idle(Some(Duration::from_secs(1))));
+        assert_eq!(vehicle.motors().target(), (0.0, 0.0));
+
+        let events = executor.take_events();
+        assert_eq!(
+            states(&events, first),
+            vec![MotionState::Started, MotionState::Cancelled]
+        );
+        assert_eq!(
+            states(&events, second),
+            vec![MotionState::Started, MotionState::Cancelled]
+        );
+    }
+
+    #[test]
+    fn sweep_moves_the_named_servo() {
+        let mock = MockBus::new();
+        let (executor, _, _) = mock_executor(&mock);
+        mock.clear();
+
+        executor.push(Motion::sweep("pan".into(), -90, 90, 0.1).unwrap());
+        assert!(executor.wait_idle(Some(Duration::from_secs(1))));
+
+        let pan: Vec<u16> = mock
+            .word_writes()
+            .iter()
+            .filter(|w| w.register == 0x20)
+            .map(|w| w.mcu_value())
+            .collect();
+        assert!(pan.len() >= 3);
+        assert!(pan.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(pan.last(), Some(&7500)); // 90 deg
+
+        let id = executor.extend(vec![
+            Motion::sweep("tilt".into(), 0, 10, 0.1).unwrap(),
+            Motion::forward(10.0, 1.0).unwrap(),
+        ])[0];
+        assert!(executor.wait_idle(Some(Duration::from_secs(1))));
+        let events = executor.take_events();
+        let failed = events.iter().find(|e| e.id == id).map(|e| e.state);
+        assert_eq!(failed, Some(MotionState::Started));
+        assert_eq!(events.last().unwrap().state, MotionState::Failed);
+        assert!(Motion::forward(10.0, -1.0).is_err());
+    }
+}
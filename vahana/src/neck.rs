@@ -1 +1,949 @@
-/* if only one line is visible in the image on the track, rotate the camera to check for the other line */
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    axel, board,
+    drive::{Servo, SERVO_FREQ},
+    error::{Error, PyError},
+    hat::RobotHat,
+    layout,
+    timing::seconds,
+};
+
+const NECK_POLL: Duration = Duration::from_millis(20);
+
+// Camera direction (deg): pan positive to the right, tilt positive up
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeckPose {
+    #[pyo3(get, set)]
+    pub pan: i32,
+    #[pyo3(get, set)]
+    pub tilt: i32,
+}
+
+#[pymethods]
+impl NeckPose {
+    #[new]
+    #[pyo3(signature = (pan = 0, tilt = 0))]
+    pub fn new(pan: i32, tilt: i32) -> Self {
+        Self { pan, tilt }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NeckConfig {
+    #[pyo3(get, set)]
+    pub pan_min: i32,
+    #[pyo3(get, set)]
+    pub pan_max: i32,
+    #[pyo3(get, set)]
+    pub tilt_min: i32,
+    #[pyo3(get, set)]
+    pub tilt_max: i32,
+    #[pyo3(get, set)]
+    pub park: NeckPose, // on park(), exit and shutdown
+    #[pyo3(get, set)]
+    pub max_speed: f64, // deg/s, see Servo.move_to()
+    #[pyo3(get, set)]
+    pub accel: f64, // deg/s^2, 0 for none
+}
+
+// PiCar-X camera mount (ref: picar-x CAM_PAN / CAM_TILT limits)
+impl Default for NeckConfig {
+    fn default() -> Self {
+        Self {
+            pan_min: -90,
+            pan_max: 90,
+            tilt_min: -35,
+            tilt_max: 65,
+            park: NeckPose::new(0, 0),
+            max_speed: 120.0,
+            accel: 600.0,
+        }
+    }
+}
+
+#[pymethods]
+impl NeckConfig {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `pose` within the limits
+    pub fn clamp(&self, pose: NeckPose) -> NeckPose {
+        NeckPose {
+            pan: pose.pan.clamp(self.pan_min, self.pan_max),
+            tilt: pose.tilt.clamp(self.tilt_min, self.tilt_max),
+        }
+    }
+
+    // Pan from one limit to the other and back, `cycles` times
+    pub fn sweep_points(&self, tilt: i32, cycles: u32) -> Vec<NeckPose> {
+        let mut points: Vec<NeckPose> = (0..cycles)
+            .flat_map(|_| [self.pan_min, self.pan_max, self.pan_min])
+            .map(|pan| self.clamp(NeckPose::new(pan, tilt)))
+            .collect();
+        points.dedup();
+        points
+    }
+
+    // `rows` x `columns` grid over the limits, top row first, panning back and forth
+    pub fn raster_points(&self, rows: u32, columns: u32) -> Vec<NeckPose> {
+        let spread = |min: i32, max: i32, count: u32, i: u32| match count {
+            0 | 1 => (min + max) / 2,
+            _ => min + ((max - min) as i64 * i as i64 / (count - 1) as i64) as i32,
+        };
+        (0..rows)
+            .flat_map(|row| {
+                let tilt = spread(self.tilt_max, self.tilt_min, rows, row);
+                (0..columns).map(move |column| {
+                    let column = if row % 2 == 0 {
+                        column
+                    } else {
+                        columns - 1 - column
+                    };
+                    NeckPose::new(spread(self.pan_min, self.pan_max, columns, column), tilt)
+                })
+            })
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl NeckConfig {
+    pub fn validate(&self) -> Result<()> {
+        let ordered = |min: i32, max: i32| -90 <= min && min < max && max <= 90;
+        if !ordered(self.pan_min, self.pan_max) || !ordered(self.tilt_min, self.tilt_max) {
+            bail!(Error::invalid(format!(
+                "NECK LIMITS PAN {}..{} TILT {}..{} MUST BE ASCENDING WITHIN -90..90",
+                self.pan_min, self.pan_max, self.tilt_min, self.tilt_max
+            )));
+        }
+        if self.clamp(self.park) != self.park {
+            bail!(Error::invalid(format!(
+                "NECK PARK POSE {:?} OUTSIDE THE LIMITS",
+                self.park
+            )));
+        }
+        if !(self.max_speed > 0.0 && self.accel >= 0.0) {
+            bail!(Error::invalid(
+                "NECK MAX SPEED MUST BE ABOVE 0 AND ACCEL 0 OR ABOVE"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+struct NeckState {
+    config: NeckConfig,
+    generation: u64, // bumped by every command, a running pattern stops when it changes
+    scanning: bool,
+    fault: Option<anyhow::Error>, // last error of a pattern, returned by the next wait()
+}
+
+// Camera pan and tilt servos moved together. Clones share the servos and any running pattern
+#[pyclass]
+#[derive(Clone)]
+pub struct Neck {
+    pan: Servo,
+    tilt: Servo,
+    state: Arc<Mutex<NeckState>>,
+}
+
+impl Neck {
+    pub fn with_servos(pan: Servo, tilt: Servo, config: NeckConfig) -> Result<Self> {
+        config.validate()?;
+        // a servo never commanded would jump to its first target instead of easing
+        for (servo, angle) in [(&pan, config.park.pan), (&tilt, config.park.tilt)] {
+            servo.set_park_angle(angle);
+            if servo.commanded().is_none() {
+                servo.park().context("NECK PARK FAILED")?;
+            }
+        }
+        let state = NeckState {
+            config,
+            generation: 0,
+            scanning: false,
+            fault: None,
+        };
+
+        Ok(Self {
+            pan,
+            tilt,
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    pub fn with_hat(hat: &RobotHat) -> Result<Self> {
+        let pins = board::current()?.pins;
+        let servos = layout::current().servos;
+        let pan = pins.pwm(&servos.camera_pan)?;
+        let tilt = pins.pwm(&servos.camera_tilt)?;
+        let pan = Servo::with_pwm(hat.pwm(pan.index())?, SERVO_FREQ)
+            .context("CAMERA PAN SERVO INIT FAILED")?;
+        let tilt = Servo::with_pwm(hat.pwm(tilt.index())?, SERVO_FREQ)
+            .context("CAMERA TILT SERVO INIT FAILED")?;
+
+        Neck::with_servos(pan, tilt, NeckConfig::default())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, NeckState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Ends a running pattern, returns the generation of the caller's command.
+    // `scanning` when the caller starts a pattern itself, under the same lock so
+    // no other command can slip in between
+    fn interrupt(&self, scanning: bool) -> u64 {
+        let mut state = self.lock();
+        state.generation += 1;
+        state.scanning = scanning;
+        state.generation
+    }
+
+    fn current(&self, generation: u64) -> bool {
+        self.lock().generation == generation
+    }
+
+    // Starts both servos towards `pose` (clamped), returns the pose aimed at
+    pub fn aim(&self, pose: NeckPose) -> Result<NeckPose> {
+        let config = self.lock().config;
+        let pose = config.clamp(pose);
+        self.pan
+            .move_profile(pose.pan, config.max_speed, config.accel)
+            .context("CAMERA PAN FAILED")?;
+        self.tilt
+            .move_profile(pose.tilt, config.max_speed, config.accel)
+            .context("CAMERA TILT FAILED")?;
+
+        Ok(pose)
+    }
+
+    // Ends any pattern and starts towards `pose`
+    pub fn look_at(&self, pose: NeckPose) -> Result<NeckPose> {
+        self.interrupt(false);
+        self.aim(pose)
+    }
+
+    // Visits `points` on a background thread, holding still for `dwell` at each
+    pub fn run_pattern(&self, points: Vec<NeckPose>, dwell: Duration) {
+        let generation = self.interrupt(true);
+
+        let neck = self.clone();
+        thread::spawn(move || {
+            let result = neck.follow(generation, &points, dwell);
+            let mut state = neck.lock();
+            if state.generation == generation {
+                state.scanning = false;
+                state.fault = result.err();
+            }
+        });
+    }
+
+    fn follow(&self, generation: u64, points: &[NeckPose], dwell: Duration) -> Result<()> {
+        for &point in points {
+            if !self.current(generation) {
+                return Ok(());
+            }
+            self.aim(point).context("NECK PATTERN FAILED")?;
+            while self.pan.moving() || self.tilt.moving() {
+                if !self.current(generation) {
+                    return Ok(());
+                }
+                sleep(NECK_POLL);
+            }
+
+            let until = Instant::now() + dwell;
+            while let Some(left) = until.checked_duration_since(Instant::now()) {
+                if !self.current(generation) {
+                    return Ok(());
+                }
+                sleep(left.min(NECK_POLL));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Blocks until no pattern runs and both servos have stopped, false on timeout
+    pub fn wait_idle(&self, timeout: Option<Duration>) -> Result<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while self.lock().scanning || self.pan.moving() || self.tilt.moving() {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            }
+            sleep(NECK_POLL);
+        }
+        if let Some(fault) = self.lock().fault.take() {
+            return Err(fault);
+        }
+
+        Ok(true)
+    }
+}
+
+#[pymethods]
+impl Neck {
+    #[new]
+    pub fn new() -> Result<Self, PyError> {
+        let hat = RobotHat::shared().context("PWM I2C INIT FAILED")?;
+        Ok(Neck::with_hat(&hat)?)
+    }
+
+    // Eases towards `pan`, `tilt` (clamped to the limits), ending any pattern
+    pub fn look(&self, pan: i32, tilt: i32) -> Result<NeckPose, PyError> {
+        Ok(self.look_at(NeckPose::new(pan, tilt))?)
+    }
+
+    // Relative to where the camera is heading
+    pub fn nudge(&self, pan: i32, tilt: i32) -> Result<NeckPose, PyError> {
+        let target = self.target();
+        self.look(target.pan + pan, target.tilt + tilt)
+    }
+
+    pub fn centre(&self) -> Result<NeckPose, PyError> {
+        self.look(0, 0)
+    }
+
+    // Straight to the park pose, no easing
+    pub fn park(&self) -> Result<(), PyError> {
+        self.interrupt(false);
+        let panned = self.pan.park();
+        let tilted = self.tilt.park();
+
+        panned.and(tilted)
+    }
+
+    // Holds both servos where they are now
+    pub fn stop(&self) -> Result<(), PyError> {
+        self.interrupt(false);
+        for servo in [&self.pan, &self.tilt] {
+            if let Some(angle) = servo.commanded() {
+                servo.angle(angle)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Pan across the limits at `tilt` (default: the current one)
+    #[pyo3(signature = (tilt = None, cycles = 1, dwell_s = 0.0))]
+    pub fn sweep(&self, tilt: Option<i32>, cycles: u32, dwell_s: f64) -> Result<(), PyError> {
+        let dwell = seconds("NECK DWELL", dwell_s)?;
+        let tilt = tilt.unwrap_or(self.target().tilt);
+        let mut points = self.config().sweep_points(tilt, cycles);
+        points.push(NeckPose::new(0, tilt));
+        self.run_pattern(points, dwell);
+
+        Ok(())
+    }
+
+    // Grid over the limits with a pause at every point, then back to the centre
+    #[pyo3(signature = (rows = 3, columns = 5, dwell_s = 0.2))]
+    pub fn raster(&self, rows: u32, columns: u32, dwell_s: f64) -> Result<(), PyError> {
+        let dwell = seconds("NECK DWELL", dwell_s)?;
+        let mut points = self.config().raster_points(rows, columns);
+        points.push(NeckPose::new(0, 0));
+        self.run_pattern(points, dwell);
+
+        Ok(())
+    }
+
+    // Returns once a pattern or move is done, False on timeout
+    #[pyo3(signature = (timeout_s = None))]
+    pub fn wait(&self, py: Python<'_>, timeout_s: Option<f64>) -> Result<bool, PyError> {
+        let timeout = timeout_s
+            .map(|timeout_s| seconds("NECK WAIT TIMEOUT", timeout_s))
+            .transpose()?;
+        Ok(py.allow_threads(|| self.wait_idle(timeout))?)
+    }
+
+    // Where the camera points now (the angles last sent), for the vision code
+    #[getter]
+    pub fn pose(&self) -> NeckPose {
+        NeckPose::new(
+            self.pan.commanded().unwrap_or(0),
+            self.tilt.commanded().unwrap_or(0),
+        )
+    }
+
+    // Where the current move ends
+    #[getter]
+    pub fn target(&self) -> NeckPose {
+        NeckPose::new(
+            self.pan.target().unwrap_or(0),
+            self.tilt.target().unwrap_or(0),
+        )
+    }
+
+    // Frames taken while moving are likely blurred
+    #[getter]
+    pub fn moving(&self) -> bool {
+        self.pan.moving() || self.tilt.moving()
+    }
+
+    #[getter]
+    pub fn scanning(&self) -> bool {
+        self.lock().scanning
+    }
+
+    #[getter]
+    pub fn config(&self) -> NeckConfig {
+        self.lock().config
+    }
+
+    #[setter]
+    pub fn set_config(&self, config: NeckConfig) -> Result<(), PyError> {
+        config.validate()?;
+        self.pan.set_park_angle(config.park.pan);
+        self.tilt.set_park_angle(config.park.tilt);
+        self.lock().config = config;
+
+        Ok(())
+    }
+
+    #[getter]
+    pub fn pan_servo(&self) -> Servo {
+        self.pan.clone()
+    }
+
+    #[getter]
+    pub fn tilt_servo(&self) -> Servo {
+        self.tilt.clone()
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> Result<bool, PyError> {
+        self.park()?;
+        Ok(false)
+    }
+}
+
+// Looking for the other lane line: when only one is seen for `patience` frames the
+// camera pans towards the missing side, up to `pan_limit`, then back to the centre
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaneSearchConfig {
+    #[pyo3(get, set)]
+    pub patience: u32, // frames with a single line before panning
+    #[pyo3(get, set)]
+    pub pan_limit: i32, // deg either side, within the neck limits
+    #[pyo3(get, set)]
+    pub hold: u32, // frames a line seen earlier stands in for a missing one
+    #[pyo3(get, set)]
+    pub reach: f64, // distance ahead the lines are refitted over (m)
+}
+
+impl Default for LaneSearchConfig {
+    fn default() -> Self {
+        Self {
+            patience: 5,
+            pan_limit: 45,
+            hold: 10,
+            reach: 0.5,
+        }
+    }
+}
+
+#[pymethods]
+impl LaneSearchConfig {
+    #[new]
+    #[pyo3(signature = (patience = 5, pan_limit = 45, hold = 10, reach = 0.5))]
+    pub fn new(patience: u32, pan_limit: i32, hold: u32, reach: f64) -> Self {
+        Self {
+            patience,
+            pan_limit,
+            hold,
+            reach,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl LaneSearchConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.patience == 0 {
+            bail!(Error::invalid(
+                "LANE SEARCH PATIENCE MUST BE 1 FRAME OR MORE"
+            ));
+        }
+        if !(1..=90).contains(&self.pan_limit) {
+            bail!(Error::invalid(format!(
+                "LANE SEARCH PAN LIMIT {} OUTSIDE 1..90",
+                self.pan_limit
+            )));
+        }
+        if !(self.reach > 0.0 && self.reach.is_finite()) {
+            bail!(Error::invalid("LANE SEARCH REACH MUST BE ABOVE 0"));
+        }
+
+        Ok(())
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaneSearchPhase {
+    Tracking,   // camera centred, counting frames with a single line
+    Searching,  // panning towards the missing line
+    Recentring, // found it or reached the limit
+}
+
+// Lane lines of one frame in the vehicle frame (x ahead, y left, metres, highest
+// power first). Empty when neither seen nor held
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaneView {
+    #[pyo3(get)]
+    pub left: Vec<f64>,
+    #[pyo3(get)]
+    pub right: Vec<f64>,
+    #[pyo3(get)]
+    pub pan: i32, // camera pan the frame was converted with (deg)
+    #[pyo3(get)]
+    pub phase: LaneSearchPhase,
+}
+
+#[pymethods]
+impl LaneView {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+// A line y = p(x) seen by the camera panned `pan` degrees to the right, refitted in
+// the vehicle frame over `reach` metres of it. The camera is taken to turn about its
+// own lens, a few millimetres off on the PiCar-X
+pub fn to_vehicle_frame(poly: &[f64], pan: i32, reach: f64) -> Option<Vec<f64>> {
+    if axel::missing(poly) {
+        return None;
+    }
+    if pan == 0 {
+        return Some(poly.to_vec());
+    }
+
+    let (sin, cos) = (pan as f64).to_radians().sin_cos();
+    let points: Vec<(f64, f64)> = (0..=20)
+        .map(|i| {
+            let x = reach * i as f64 / 20.0;
+            let y = axel::eval(poly, x);
+            (x * cos + y * sin, y * cos - x * sin)
+        })
+        .collect();
+    // turned, even a straight line gets a slope
+    let degree = poly.len().saturating_sub(1).clamp(1, 3);
+
+    axel::fit(&points, degree)
+}
+
+// Frame by frame, so it fits in the driving loop: update() only starts servo moves,
+// the neck's servo tickers carry them out in the background
+#[pyclass]
+pub struct LaneSearch {
+    neck: Neck,
+    config: LaneSearchConfig,
+    phase: LaneSearchPhase,
+    toward: i32,                        // side of the missing line, -1 left or 1 right
+    single: u32,                        // consecutive frames with one line
+    held: [Option<(Vec<f64>, u32)>; 2], // last left / right sighting and its age
+}
+
+impl LaneSearch {
+    pub fn with_neck(neck: Neck, config: LaneSearchConfig) -> Result<Self> {
+        config.validate()?;
+
+        Ok(Self {
+            neck,
+            config,
+            phase: LaneSearchPhase::Tracking,
+            toward: 0,
+            single: 0,
+            held: [None, None],
+        })
+    }
+
+    // Takes the lines of the latest frame in the camera's road frame, e.g. from
+    // LaneDetector.fit_poly, all zeros for a line not seen. `pan` is Neck.pose
+    // read when the frame was captured, the camera keeps turning during detection
+    pub fn update(&mut self, left: &[f64], right: &[f64], pan: i32) -> Result<LaneView> {
+        let left = to_vehicle_frame(left, pan, self.config.reach);
+        let right = to_vehicle_frame(right, pan, self.config.reach);
+
+        match self.phase {
+            LaneSearchPhase::Tracking => {
+                if left.is_some() != right.is_some() {
+                    self.single += 1;
+                } else {
+                    self.single = 0;
+                }
+                if self.single >= self.config.patience {
+                    self.toward = if left.is_none() { -1 } else { 1 };
+                    self.pan_to(self.toward * self.config.pan_limit)
+                        .context("LANE SEARCH FAILED")?;
+                    self.phase = LaneSearchPhase::Searching;
+                }
+            }
+            LaneSearchPhase::Searching => {
+                // the other line is often out of view by then, held from earlier frames
+                let found = match self.toward {
+                    -1 => left.is_some(),
+                    _ => right.is_some(),
+                };
+                // or the pan limit reached without finding it
+                if found || !self.neck.moving() {
+                    self.recentre()?;
+                }
+            }
+            LaneSearchPhase::Recentring => {
+                if !self.neck.moving() {
+                    self.phase = LaneSearchPhase::Tracking;
+                    self.single = 0;
+                }
+            }
+        }
+
+        let hold = self.config.hold;
+        let [left, right] = [(0, left), (1, right)].map(|(side, seen)| {
+            let held = &mut self.held[side];
+            match seen {
+                Some(poly) => *held = Some((poly, 0)),
+                None => {
+                    *held = held
+                        .take()
+                        .map(|(poly, age)| (poly, age + 1))
+                        .filter(|&(_, age)| age <= hold)
+                }
+            }
+            held.as_ref()
+                .map(|(poly, _)| poly.clone())
+                .unwrap_or_default()
+        });
+
+        Ok(LaneView {
+            left,
+            right,
+            pan,
+            phase: self.phase,
+        })
+    }
+
+    fn pan_to(&self, pan: i32) -> Result<NeckPose> {
+        self.neck
+            .look_at(NeckPose::new(pan, self.neck.target().tilt))
+    }
+
+    pub fn recentre(&mut self) -> Result<()> {
+        self.pan_to(0).context("LANE SEARCH RECENTRE FAILED")?;
+        self.phase = LaneSearchPhase::Recentring;
+
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl LaneSearch {
+    #[new]
+    #[pyo3(signature = (neck, config = None))]
+    pub fn new(neck: PyRef<'_, Neck>, config: Option<LaneSearchConfig>) -> Result<Self, PyError> {
+        Ok(LaneSearch::with_neck(
+            neck.clone(),
+            config.unwrap_or_default(),
+        )?)
+    }
+
+    #[pyo3(name = "update")]
+    fn py_update(
+        &mut self,
+        left: Vec<f64>,
+        right: Vec<f64>,
+        pan: i32,
+    ) -> Result<LaneView, PyError> {
+        Ok(self.update(&left, &right, pan)?)
+    }
+
+    // Back to the centre and forgets held lines, e.g. when the lane was lost altogether
+    pub fn reset(&mut self) -> Result<(), PyError> {
+        self.held = [None, None];
+        self.single = 0;
+        Ok(self.recentre()?)
+    }
+
+    #[getter]
+    pub fn phase(&self) -> LaneSearchPhase {
+        self.phase
+    }
+
+    #[getter]
+    pub fn neck(&self) -> Neck {
+        self.neck.clone()
+    }
+
+    #[getter]
+    pub fn config(&self) -> LaneSearchConfig {
+        self.config
+    }
+
+    #[setter]
+    pub fn set_config(&mut self, config: LaneSearchConfig) -> Result<(), PyError> {
+        config.validate()?;
+        self.config = config;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockBus;
+
+    fn mock_neck(mock: &MockBus) -> Neck {
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let pan = Servo::with_pwm(hat.pwm(0).unwrap(), SERVO_FREQ).unwrap();
+        let tilt = Servo::with_pwm(hat.pwm(1).unwrap(), SERVO_FREQ).unwrap();
+        let config = NeckConfig {
+            max_speed: 3000.0,
+            accel: 0.0,
+            ..NeckConfig::default()
+        };
+        Neck::with_servos(pan, tilt, config).unwrap()
+    }
+
+    #[test]
+    fn look_clamps_and_nudge_is_relative() {
+        let mock = MockBus::new();
+        let neck = mock_neck(&mock);
+
+        assert_eq!(neck.look(20, 80).unwrap(), NeckPose::new(20, 65));
+        assert!(neck.wait_idle(Some(Duration::from_secs(1))).unwrap());
+        assert_eq!(neck.pose(), NeckPose::new(20, 65));
+
+        assert_eq!(neck.nudge(-30, -10).unwrap(), NeckPose::new(-10, 55));
+        assert_eq!(neck.target(), NeckPose::new(-10, 55));
+        assert!(neck.wait_idle(Some(Duration::from_secs(1))).unwrap());
+        assert_eq!(neck.pose(), NeckPose::new(-10, 55));
+
+        neck.centre().unwrap();
+        assert!(neck.wait_idle(Some(Duration::from_secs(1))).unwrap());
+        assert_eq!(neck.pose(), NeckPose::new(0, 0));
+        let pan = mock.word_writes().into_iter().rfind(|w| w.register == 0x20);
+        assert_eq!(pan.unwrap().mcu_value(), 4500);
+
+        let config = NeckConfig {
+            park: NeckPose::new(0, -40),
+            ..NeckConfig::default()
+        };
+        assert!(neck.set_config(config).is_err());
+    }
+
+    #[test]
+    fn patterns_visit_the_limits() {
+        let config = NeckConfig::default();
+        assert_eq!(
+            config.sweep_points(10, 2),
+            vec![
+                NeckPose::new(-90, 10),
+                NeckPose::new(90, 10),
+                NeckPose::new(-90, 10),
+                NeckPose::new(90, 10),
+                NeckPose::new(-90, 10),
+            ]
+        );
+
+        let raster = config.raster_points(2, 3);
+        let expected = [
+            (-90, 65),
+            (0, 65),
+            (90, 65),
+            (90, -35),
+            (0, -35),
+            (-90, -35),
+        ];
+        assert_eq!(
+            raster,
+            expected
+                .map(|(pan, tilt)| NeckPose::new(pan, tilt))
+                .to_vec()
+        );
+        assert_eq!(config.raster_points(1, 1), vec![NeckPose::new(0, 15)]);
+    }
+
+    #[test]
+    fn sweep_runs_in_the_background_until_interrupted() {
+        let mock = MockBus::new();
+        let neck = mock_neck(&mock);
+
+        neck.sweep(Some(0), 1, 0.0).unwrap();
+        assert!(neck.scanning());
+        assert!(neck.wait_idle(Some(Duration::from_secs(2))).unwrap());
+        assert!(!neck.scanning());
+        assert_eq!(neck.pose(), NeckPose::new(0, 0));
+        let pans: Vec<u16> = mock
+            .word_writes()
+            .iter()
+            .filter(|w| w.register == 0x20)
+            .map(|w| w.mcu_value())
+            .collect();
+        assert!(pans.contains(&1500) && pans.contains(&7500)); // both limits
+
+        neck.raster(3, 5, 1.0).unwrap();
+        sleep(Duration::from_millis(100));
+        neck.look(45, 0).unwrap();
+        assert!(!neck.scanning());
+        assert!(neck.wait_idle(Some(Duration::from_secs(1))).unwrap());
+        sleep(Duration::from_millis(100));
+        assert_eq!(neck.pose(), NeckPose::new(45, 0));
+    }
+
+    #[test]
+    fn panned_lines_are_turned_into_the_vehicle_frame() {
+        // parallel to the camera panned 30 deg right: heads off to the right
+        let pan = 30f64.to_radians();
+        let line = to_vehicle_frame(&[0.0, 0.1], 30, 0.5).unwrap();
+        assert!((line[0] + pan.tan()).abs() < 1e-9);
+        assert!((line[1] - 0.1 / pan.cos()).abs() < 1e-9);
+
+        // the right line of a straight lane, seen looking 20 deg to the right
+        let pan = 20f64.to_radians();
+        let seen = [pan.tan(), -0.15 / pan.cos()];
+        let line = to_vehicle_frame(&seen, 20, 0.5).unwrap();
+        assert!(line[0].abs() < 1e-9 && (line[1] + 0.15).abs() < 1e-9);
+
+        assert_eq!(to_vehicle_frame(&[0.0; 4], 20, 0.5), None);
+        assert_eq!(
+            to_vehicle_frame(&[0.1, -0.15], 0, 0.5),
+            Some(vec![0.1, -0.15])
+        );
+    }
+
+    #[test]
+    fn search_pans_towards_the_missing_line_then_recentres() {
+        let mock = MockBus::new();
+        let neck = mock_neck(&mock);
+        // slow enough to see the line half way
+        let slow = NeckConfig {
+            max_speed: 60.0,
+            accel: 0.0,
+            ..NeckConfig::default()
+        };
+        neck.set_config(slow).unwrap();
+        let config = LaneSearchConfig::new(3, 40, 3, 0.5);
+        let mut search = LaneSearch::with_neck(neck.clone(), config).unwrap();
+        let left = [0.0, 0.15];
+        let right = [0.0, -0.15];
+        let gone = [0.0; 2];
+
+        // a frame with both lines resets the count
+        for lines in [&gone, &gone, &right, &gone, &gone] {
+            search.update(&left, lines, neck.pose().pan).unwrap();
+        }
+        assert_eq!(search.phase(), LaneSearchPhase::Tracking);
+        let view = search.update(&left, &gone, neck.pose().pan).unwrap();
+        assert_eq!(search.phase(), LaneSearchPhase::Searching);
+        assert_eq!(neck.target(), NeckPose::new(40, 0));
+        assert_eq!(view.right, right); // held from the frame before
+
+        // found while turning, the left line out of view by now
+        sleep(Duration::from_millis(200));
+        let pan = neck.pose().pan;
+        assert!((1..40).contains(&pan), "{pan}");
+        let angle = (pan as f64).to_radians();
+        let seen = [angle.tan(), -0.15 / angle.cos()];
+        sleep(Duration::from_millis(100)); // detection time, the camera turns on
+        assert!(neck.pose().pan > pan);
+        let view = search.update(&gone, &seen, pan).unwrap();
+        assert_eq!(view.pan, pan);
+        assert!(view.right[0].abs() < 1e-9 && (view.right[1] + 0.15).abs() < 1e-9);
+        assert_eq!(view.left, left); // held
+        assert_eq!(view.phase, LaneSearchPhase::Recentring);
+        assert_eq!(neck.target(), NeckPose::new(0, 0));
+
+        assert!(neck.wait_idle(Some(Duration::from_secs(2))).unwrap());
+        search.update(&gone, &right, neck.pose().pan).unwrap();
+        assert_eq!(search.phase(), LaneSearchPhase::Tracking);
+    }
+
+    #[test]
+    fn search_eases_from_a_fresh_neck() {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let pan = Servo::with_pwm(hat.pwm(0).unwrap(), SERVO_FREQ).unwrap();
+        let tilt = Servo::with_pwm(hat.pwm(1).unwrap(), SERVO_FREQ).unwrap();
+        assert_eq!(pan.commanded(), None);
+
+        let neck = Neck::with_servos(pan, tilt, NeckConfig::default()).unwrap();
+        assert_eq!(neck.pose(), NeckPose::new(0, 0));
+        let mut search = LaneSearch::with_neck(neck.clone(), LaneSearchConfig::default()).unwrap();
+        for _ in 0..5 {
+            search.update(&[0.0, 0.15], &[0.0; 2], 0).unwrap();
+        }
+        assert_eq!(search.phase(), LaneSearchPhase::Searching);
+
+        // still on its way on the next frame, not already at the limit
+        sleep(Duration::from_millis(40));
+        assert!(neck.moving() && neck.pose().pan < 45);
+        search.update(&[0.0, 0.15], &[0.0; 2], 0).unwrap();
+        assert_eq!(search.phase(), LaneSearchPhase::Searching);
+    }
+
+    #[test]
+    fn search_gives_up_at_the_pan_limit() {
+        let mock = MockBus::new();
+        let neck = mock_neck(&mock);
+        let mut search = LaneSearch::with_neck(neck.clone(), LaneSearchConfig::default()).unwrap();
+        let gone = [0.0; 4];
+
+        for _ in 0..5 {
+            search.update(&gone, &[0.0, 0.0, 0.0, -0.15], 0).unwrap();
+        }
+        assert_eq!(neck.target(), NeckPose::new(-45, 0));
+        assert!(neck.wait_idle(Some(Duration::from_secs(1))).unwrap());
+
+        // the held line runs out too
+        let view = search.update(&gone, &gone, neck.pose().pan).unwrap();
+        assert_eq!(search.phase(), LaneSearchPhase::Recentring);
+        assert_eq!(view.pan, -45);
+        assert!(!view.right.is_empty() && view.left.is_empty());
+        for _ in 0..10 {
+            search.update(&gone, &gone, neck.pose().pan).unwrap();
+        }
+        assert!(search
+            .update(&gone, &gone, neck.pose().pan)
+            .unwrap()
+            .right
+            .is_empty());
+
+        assert!(LaneSearch::with_neck(neck, LaneSearchConfig::new(0, 45, 10, 0.5)).is_err());
+    }
+}
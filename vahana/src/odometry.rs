@@ -0,0 +1,507 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{
+    collections::VecDeque,
+    f64::consts::PI,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, PyError},
+    vehicle::VehicleGeometry,
+};
+
+const ODOMETRY_STEP: Duration = Duration::from_millis(20); // longer gaps are integrated in steps
+const MAX_TRAIL: usize = 4096; // oldest trajectory points are dropped past this
+
+type Matrix = [[f64; 3]; 3];
+
+// Measured ground speed for a commanded motor speed, plus how far to trust it.
+// Both errors grow as random walks over the distance travelled
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OdometryConfig {
+    #[pyo3(get, set)]
+    pub velocity_curve: Vec<(f64, f64)>, // (speed 0..100, m/s) points, speed ascending
+    #[pyo3(get, set)]
+    pub speed_noise: f64, // std dev of the distance error after 1 m (m)
+    #[pyo3(get, set)]
+    pub steering_noise: f64, // std dev of the real front wheel angle (deg)
+}
+
+// Rough PiCar-X figures on a hard floor, measure your own with a tape
+impl Default for OdometryConfig {
+    fn default() -> Self {
+        Self {
+            velocity_curve: vec![(0.0, 0.0), (10.0, 0.0), (100.0, 0.35)],
+            speed_noise: 0.1,
+            steering_noise: 2.0,
+        }
+    }
+}
+
+#[pymethods]
+impl OdometryConfig {
+    #[new]
+    #[pyo3(signature = (velocity_curve = None, speed_noise = 0.1, steering_noise = 2.0))]
+    pub fn new(
+        velocity_curve: Option<Vec<(f64, f64)>>,
+        speed_noise: f64,
+        steering_noise: f64,
+    ) -> Self {
+        Self {
+            velocity_curve: velocity_curve.unwrap_or(Self::default().velocity_curve),
+            speed_noise,
+            steering_noise,
+        }
+    }
+
+    // Ground speed (m/s) for a motor speed (-100..100), linear between the curve points
+    pub fn velocity(&self, speed: f64) -> f64 {
+        let magnitude = speed.abs();
+        let curve = &self.velocity_curve;
+        let Some(upper) = curve.iter().position(|&(point, _)| point >= magnitude) else {
+            return curve.last().map_or(0.0, |&(_, v)| v).copysign(speed);
+        };
+        if upper == 0 {
+            return curve[0].1.copysign(speed);
+        }
+
+        let (s0, v0) = curve[upper - 1];
+        let (s1, v1) = curve[upper];
+        (v0 + (v1 - v0) * (magnitude - s0) / (s1 - s0)).copysign(speed)
+    }
+
+    #[staticmethod]
+    pub fn load(path: &str) -> Result<Self, PyError> {
+        Ok(read_odometry_config(Path::new(path))?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), PyError> {
+        let text = toml::to_string(self).context("SERIALISING ODOMETRY CONFIG")?;
+        fs::write(path, text)
+            .map_err(Error::from)
+            .with_context(|| format!("WRITING ODOMETRY CONFIG {path}"))?;
+
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl OdometryConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.velocity_curve.is_empty() {
+            bail!(Error::invalid("ODOMETRY VELOCITY CURVE IS EMPTY"));
+        }
+        let finite = |&(speed, velocity): &(f64, f64)| speed.is_finite() && velocity.is_finite();
+        if !self.velocity_curve.iter().all(finite) {
+            bail!(Error::invalid("ODOMETRY VELOCITY CURVE MUST BE FINITE"));
+        }
+        if self.velocity_curve.windows(2).any(|w| w[0].0 >= w[1].0) {
+            bail!(Error::invalid(
+                "ODOMETRY VELOCITY CURVE SPEEDS MUST BE ASCENDING"
+            ));
+        }
+        if !(self.speed_noise >= 0.0 && self.steering_noise >= 0.0) {
+            bail!(Error::invalid("ODOMETRY NOISE MUST BE 0 OR ABOVE"));
+        }
+
+        Ok(())
+    }
+}
+
+fn read_odometry_config(path: &Path) -> Result<OdometryConfig> {
+    let text = fs::read_to_string(path)
+        .map_err(Error::from)
+        .with_context(|| format!("READING ODOMETRY CONFIG {}", path.display()))?;
+    let config: OdometryConfig = toml::from_str(&text)
+        .map_err(Error::from)
+        .with_context(|| format!("PARSING ODOMETRY CONFIG {}", path.display()))?;
+    config
+        .validate()
+        .with_context(|| format!("INVALID ODOMETRY CONFIG {}", path.display()))?;
+
+    Ok(config)
+}
+
+// Rear axle centre in the frame the estimate was reset in: x ahead, y to the left,
+// heading counter-clockwise (rad). Covariance over (x, y, heading)
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    #[pyo3(get)]
+    pub x: f64,
+    #[pyo3(get)]
+    pub y: f64,
+    #[pyo3(get)]
+    pub heading: f64,
+    #[pyo3(get)]
+    pub covariance: Matrix,
+}
+
+impl Pose {
+    pub fn at(x: f64, y: f64, heading: f64) -> Self {
+        Self {
+            x,
+            y,
+            heading: wrap_angle(heading),
+            covariance: [[0.0; 3]; 3],
+        }
+    }
+}
+
+#[pymethods]
+impl Pose {
+    #[getter]
+    pub fn heading_deg(&self) -> f64 {
+        self.heading.to_degrees()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+fn wrap_angle(angle: f64) -> f64 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 3]; 3];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    product
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    let mut transposed = [[0.0; 3]; 3];
+    for (i, row) in a.iter().enumerate() {
+        for (j, &cell) in row.iter().enumerate() {
+            transposed[j][i] = cell;
+        }
+    }
+    transposed
+}
+
+pub struct OdometryState {
+    pub config: OdometryConfig,
+    pub wheelbase: f64,
+    pose: Pose,
+    speed: f64,    // commanded motor speed (-100..100), mean of both sides
+    steering: f64, // commanded front wheel angle (deg), positive to the right
+    last: Instant,
+    trail: VecDeque<(f64, f64)>,
+}
+
+impl OdometryState {
+    // Integrates up to now with the previous command, then switches to the new one
+    pub fn set_speed(&mut self, speed: f64) {
+        self.sync();
+        self.speed = speed;
+    }
+
+    pub fn set_steering(&mut self, angle: f64) {
+        self.sync();
+        self.steering = angle;
+    }
+
+    pub fn inputs(&self) -> (f64, f64) {
+        (self.speed, self.steering)
+    }
+
+    pub fn sync(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = now;
+        self.integrate(elapsed);
+    }
+
+    // Advances the pose by `elapsed` at the current speed and steering angle
+    pub fn integrate(&mut self, mut elapsed: Duration) {
+        while !elapsed.is_zero() {
+            let dt = elapsed.min(ODOMETRY_STEP);
+            elapsed -= dt;
+            self.step(dt.as_secs_f64());
+        }
+    }
+
+    // Bicycle model: exact arc for the mean, linearised for the covariance
+    fn step(&mut self, dt: f64) {
+        let distance = self.config.velocity(self.speed) * dt;
+        if distance == 0.0 {
+            return;
+        }
+
+        let steering = self.steering.to_radians();
+        let curvature = -steering.tan() / self.wheelbase; // positive steering turns clockwise
+        let turn = curvature * distance;
+        let Pose { x, y, heading, .. } = self.pose;
+        let (dx, dy) = if turn.abs() < 1e-9 {
+            (distance * heading.cos(), distance * heading.sin())
+        } else {
+            (
+                ((heading + turn).sin() - heading.sin()) / curvature,
+                (heading.cos() - (heading + turn).cos()) / curvature,
+            )
+        };
+
+        let mid = heading + turn / 2.0;
+        let jacobian = [
+            [1.0, 0.0, -distance * mid.sin()],
+            [0.0, 1.0, distance * mid.cos()],
+            [0.0, 0.0, 1.0],
+        ];
+        let distance_var = self.config.speed_noise.powi(2) * distance.abs();
+        let heading_rate =
+            self.config.steering_noise.to_radians() / (self.wheelbase * steering.cos().powi(2)); // d(heading)/d(steering) per metre
+        let noise_gain = [mid.cos(), mid.sin(), curvature];
+        let mut covariance = multiply(
+            &multiply(&jacobian, &self.pose.covariance),
+            &transpose(&jacobian),
+        );
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell += noise_gain[i] * noise_gain[j] * distance_var;
+            }
+        }
+        covariance[2][2] += heading_rate.powi(2) * distance.abs();
+
+        self.pose = Pose {
+            x: x + dx,
+            y: y + dy,
+            heading: wrap_angle(heading + turn),
+            covariance,
+        };
+        if self.trail.len() == MAX_TRAIL {
+            self.trail.pop_front();
+        }
+        self.trail.push_back((self.pose.x, self.pose.y));
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.pose
+    }
+
+    pub fn trail(&self) -> Vec<(f64, f64)> {
+        self.trail.iter().copied().collect()
+    }
+
+    // Certain pose, trajectory cleared; the current command keeps running
+    pub fn reset(&mut self, pose: Pose) {
+        self.sync();
+        self.pose = pose;
+        self.trail.clear();
+        self.trail.push_back((pose.x, pose.y));
+    }
+}
+
+// Dead reckoning from commands alone (there are no wheel encoders). Shared by
+// the Motors and steering Servo it is attached to, which report every write
+#[pyclass]
+#[derive(Clone)]
+pub struct Odometry {
+    inner: Arc<Mutex<OdometryState>>,
+}
+
+impl Odometry {
+    pub fn with_config(config: OdometryConfig, wheelbase: f64) -> Result<Self> {
+        config.validate()?;
+        if wheelbase.is_nan() || wheelbase <= 0.0 {
+            bail!(Error::invalid("ODOMETRY WHEELBASE MUST BE ABOVE 0"));
+        }
+
+        let state = OdometryState {
+            config,
+            wheelbase,
+            pose: Pose::at(0.0, 0.0, 0.0),
+            speed: 0.0,
+            steering: 0.0,
+            last: Instant::now(),
+            trail: VecDeque::from([(0.0, 0.0)]),
+        };
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, OdometryState> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[pymethods]
+impl Odometry {
+    #[new]
+    #[pyo3(signature = (config = None, wheelbase = VehicleGeometry::default().wheelbase))]
+    pub fn new(config: Option<OdometryConfig>, wheelbase: f64) -> Result<Self, PyError> {
+        Ok(Odometry::with_config(
+            config.unwrap_or_default(),
+            wheelbase,
+        )?)
+    }
+
+    // Estimate as of now
+    #[getter]
+    pub fn pose(&self) -> Pose {
+        let mut state = self.lock();
+        state.sync();
+        state.pose()
+    }
+
+    // Positions since the last reset, oldest first, for plotting
+    #[getter]
+    pub fn trajectory(&self) -> Vec<(f64, f64)> {
+        let mut state = self.lock();
+        state.sync();
+        state.trail()
+    }
+
+    // `heading` in radians
+    #[pyo3(signature = (x = 0.0, y = 0.0, heading = 0.0))]
+    pub fn reset(&self, x: f64, y: f64, heading: f64) {
+        self.lock().reset(Pose::at(x, y, heading));
+    }
+
+    // For cars driven without Motors / Servo: motor speed (-100..100) and steering (deg)
+    pub fn command(&self, speed: f64, steering: f64) {
+        let mut state = self.lock();
+        state.set_speed(speed);
+        state.set_steering(steering);
+    }
+
+    #[getter]
+    pub fn config(&self) -> OdometryConfig {
+        self.lock().config.clone()
+    }
+
+    // Motion so far is integrated with the old config
+    #[setter]
+    pub fn set_config(&self, config: OdometryConfig) -> Result<(), PyError> {
+        config.validate()?;
+        let mut state = self.lock();
+        state.sync();
+        state.config = config;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        drive::{Motor, Motors, Servo, SERVO_FREQ},
+        hat::RobotHat,
+        mock::{MockBus, MockPin},
+        vehicle::Vehicle,
+    };
+
+    fn linear(top: f64) -> OdometryConfig {
+        OdometryConfig::new(Some(vec![(0.0, 0.0), (100.0, top)]), 0.1, 2.0)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn velocity_curve_interpolates_symmetrically() {
+        let config = OdometryConfig::default();
+
+        assert_eq!(config.velocity(5.0), 0.0);
+        assert!(close(config.velocity(55.0), 0.175));
+        assert!(close(config.velocity(-55.0), -0.175));
+        assert!(close(config.velocity(120.0), 0.35));
+        assert!(
+            OdometryConfig::new(Some(vec![(50.0, 0.1), (10.0, 0.2)]), 0.1, 2.0)
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn straight_line_and_reset() {
+        let odometry = Odometry::with_config(linear(0.4), 0.1).unwrap();
+        let mut state = odometry.lock();
+        state.speed = 50.0;
+        state.integrate(Duration::from_secs(2));
+
+        let pose = state.pose();
+        assert!(close(pose.x, 0.4) && close(pose.y, 0.0) && close(pose.heading, 0.0));
+        assert!(close(pose.covariance[0][0], 0.1f64.powi(2) * 0.4));
+        assert!(pose.covariance[1][1] > 0.0 && pose.covariance[2][2] > 0.0);
+        assert_eq!(state.trail().len(), 101);
+
+        state.reset(Pose::at(1.0, 2.0, 3.0 * PI));
+        assert_eq!(state.pose().covariance, [[0.0; 3]; 3]);
+        assert!(close(state.pose().heading, PI));
+        assert_eq!(state.trail(), vec![(1.0, 2.0)]);
+    }
+
+    #[test]
+    fn right_turn_follows_the_circle() {
+        let odometry = Odometry::with_config(linear(0.5), 0.1).unwrap();
+        let mut state = odometry.lock();
+        state.speed = 100.0;
+        state.steering = 30.0;
+        let radius = 0.1 / 30f64.to_radians().tan();
+
+        // a quarter circle clockwise ends ahead and to the right, facing right
+        let quarter = PI / 2.0 * radius / 0.5;
+        state.integrate(Duration::from_secs_f64(quarter));
+        let pose = state.pose();
+        assert!((pose.x - radius).abs() < 1e-3);
+        assert!((pose.y + radius).abs() < 1e-3);
+        assert!((pose.heading + PI / 2.0).abs() < 1e-3);
+
+        let trace = |pose: Pose| (0..3).map(|i| pose.covariance[i][i]).sum::<f64>();
+        let before = trace(pose);
+        state.integrate(Duration::from_secs_f64(3.0 * quarter));
+        let pose = state.pose();
+        assert!(pose.x.abs() < 1e-3 && pose.y.abs() < 1e-3 && pose.heading.abs() < 1e-3);
+        assert!(trace(pose) > before);
+    }
+
+    #[test]
+    fn motors_and_steering_report_commands() {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let left = Motor::with_parts(hat.pwm(12).unwrap(), Box::new(MockPin::new()), 50.0);
+        let right = Motor::with_parts(hat.pwm(13).unwrap(), Box::new(MockPin::new()), 50.0);
+        let motors = Motors::with_motors(left.unwrap(), right.unwrap());
+        let steering = Servo::with_pwm(hat.pwm(2).unwrap(), SERVO_FREQ).unwrap();
+        let mut vehicle =
+            Vehicle::with_parts(motors, steering, VehicleGeometry::default()).unwrap();
+
+        let odometry = vehicle.enable_odometry(None).unwrap();
+        vehicle.drive_angle(40.0, -20.0).unwrap();
+        let (speed, steering) = odometry.lock().inputs();
+        assert!(close(speed, 40.0));
+        assert_eq!(steering, -20.0);
+
+        vehicle.emergency_stop().unwrap();
+        assert_eq!(odometry.lock().inputs().0, 0.0);
+        assert!(vehicle.odometry().is_some());
+    }
+}
@@ -0,0 +1,186 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    error::{Error, PyError},
+    protocol::{AdcChannel, Channel},
+};
+
+// Header names -> BCM numbers (ref: robot-hat pin.py)
+pub(crate) const PINS_V1: [(&str, u8); 25] = [
+    ("D0", 17),
+    ("D1", 4),
+    ("D2", 27),
+    ("D3", 22),
+    ("D4", 23),
+    ("D5", 24),
+    ("D6", 25),
+    ("D7", 4),
+    ("D8", 5),
+    ("D9", 6),
+    ("D10", 12),
+    ("D11", 13),
+    ("D12", 19),
+    ("D13", 16),
+    ("D14", 26),
+    ("D15", 20),
+    ("D16", 21),
+    ("SW", 25),
+    ("USER", 25),
+    ("LED", 26),
+    ("BOARD_TYPE", 12),
+    ("RST", 16),
+    ("BLEINT", 13),
+    ("BLERST", 20),
+    ("MCURST", 5),
+];
+
+// Same header, MCU reset moved to BCM 21
+pub(crate) const PINS_V2: [(&str, u8); 25] = [
+    ("D0", 17),
+    ("D1", 4),
+    ("D2", 27),
+    ("D3", 22),
+    ("D4", 23),
+    ("D5", 24),
+    ("D6", 25),
+    ("D7", 4),
+    ("D8", 5),
+    ("D9", 6),
+    ("D10", 12),
+    ("D11", 13),
+    ("D12", 19),
+    ("D13", 16),
+    ("D14", 26),
+    ("D15", 20),
+    ("D16", 21),
+    ("SW", 25),
+    ("USER", 25),
+    ("LED", 26),
+    ("BOARD_TYPE", 12),
+    ("RST", 16),
+    ("BLEINT", 13),
+    ("BLERST", 20),
+    ("MCURST", 21),
+];
+
+// A robot-hat header name resolved to what drives it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pin {
+    Gpio(u8),           // "D4", "MCURST", ... -> BCM number
+    Pwm(Channel),       // "P0" - "P13"
+    Analog(AdcChannel), // "A0" - "A7"
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinMap {
+    digital: &'static [(&'static str, u8)],
+}
+
+impl PinMap {
+    pub const fn new(digital: &'static [(&'static str, u8)]) -> Self {
+        Self { digital }
+    }
+
+    pub fn resolve(&self, name: &str) -> Result<Pin> {
+        let numbered = |prefix: &str| {
+            name.strip_prefix(prefix)
+                .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|n| n.parse::<u8>().ok())
+        };
+
+        if let Some(channel) = numbered("P") {
+            return Ok(Pin::Pwm(Channel::new(channel)?));
+        }
+        if let Some(channel) = numbered("A") {
+            return Ok(Pin::Analog(AdcChannel::new(channel)?));
+        }
+
+        self.digital
+            .iter()
+            .find(|(pin, _)| *pin == name)
+            .map(|&(_, bcm)| Pin::Gpio(bcm))
+            .ok_or_else(|| Error::invalid(format!("UNKNOWN PIN {name:?}")).into())
+    }
+
+    // BCM number of a digital pin
+    pub fn gpio(&self, name: &str) -> Result<u8> {
+        match self.resolve(name)? {
+            Pin::Gpio(bcm) => Ok(bcm),
+            _ => bail!(Error::invalid(format!("PIN {name:?} IS NOT A GPIO PIN"))),
+        }
+    }
+
+    pub fn pwm(&self, name: &str) -> Result<Channel> {
+        match self.resolve(name)? {
+            Pin::Pwm(channel) => Ok(channel),
+            _ => bail!(Error::invalid(format!(
+                "PIN {name:?} IS NOT A PWM CHANNEL (P0-P13)"
+            ))),
+        }
+    }
+
+    pub fn analog(&self, name: &str) -> Result<AdcChannel> {
+        match self.resolve(name)? {
+            Pin::Analog(channel) => Ok(channel),
+            _ => bail!(Error::invalid(format!(
+                "PIN {name:?} IS NOT AN ADC CHANNEL (A0-A7)"
+            ))),
+        }
+    }
+}
+
+#[pymethods]
+impl PinMap {
+    #[pyo3(name = "gpio")]
+    fn py_gpio(&self, name: &str) -> Result<u8, PyError> {
+        Ok(self.gpio(name)?)
+    }
+
+    #[pyo3(name = "pwm")]
+    fn py_pwm(&self, name: &str) -> Result<u8, PyError> {
+        Ok(self.pwm(name)?.index())
+    }
+
+    #[pyo3(name = "analog")]
+    fn py_analog(&self, name: &str) -> Result<u8, PyError> {
+        Ok(self.analog(name)?.index())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.digital.iter().map(|&(name, _)| name).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_resolve_to_bcm_and_channels() {
+        let v1 = PinMap::new(&PINS_V1);
+        let v2 = PinMap::new(&PINS_V2);
+
+        assert_eq!(v1.gpio("D2").unwrap(), 27);
+        assert_eq!(v1.gpio("MCURST").unwrap(), 5);
+        assert_eq!(v2.gpio("MCURST").unwrap(), 21);
+        assert_eq!(v1.pwm("P12").unwrap().index(), 12);
+        assert_eq!(v1.analog("A4").unwrap().index(), 4);
+    }
+
+    #[test]
+    fn wrong_kind_or_unknown_names_are_rejected() {
+        let map = PinMap::new(&PINS_V1);
+
+        assert!(map.gpio("P12").is_err());
+        assert!(map.pwm("D4").is_err());
+        assert!(map.pwm("P14").is_err());
+        assert!(map.analog("A8").is_err());
+        assert!(map.resolve("P").is_err());
+        assert!(map.resolve("D42").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{bail, Context, Result};
+
+use crate::{error::Error, hal::I2cBus};
+
+// Robot HAT MCU register map (ref: robot-hat)
+const REG_ADC: u8 = 0x10; // A7 = 0x10 ... A0 = 0x17
+const REG_PW: u8 = 0x20; // REG_CHN, one per channel
+const REG_PSC: u8 = 0x40; // REG_PSC, one per timer
+const REG_PER: u8 = 0x44; // REG_ARR, one per timer
+
+pub const PWM_CHANNELS: u8 = 14; // P0 - P13
+pub const PWM_TIMERS: u8 = 4; // four channels per timer
+pub const ADC_CHANNELS: u8 = 8; // A0 - A7
+
+// PWM output P0-P13
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Channel(u8);
+
+impl Channel {
+    pub fn new(channel: u8) -> Result<Self> {
+        if channel >= PWM_CHANNELS {
+            bail!(Error::invalid(format!(
+                "PWM CHANNEL P{channel} OUT OF RANGE (P0-P{})",
+                PWM_CHANNELS - 1
+            )));
+        }
+
+        Ok(Self(channel))
+    }
+
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn timer(self) -> Timer {
+        Timer(self.0 / 4)
+    }
+}
+
+// Prescaler/period timer 0-3, shared by channels 4n..4n+3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timer(u8);
+
+impl Timer {
+    pub fn new(timer: u8) -> Result<Self> {
+        if timer >= PWM_TIMERS {
+            bail!(Error::invalid(format!(
+                "PWM TIMER {timer} OUT OF RANGE (0-{})",
+                PWM_TIMERS - 1
+            )));
+        }
+
+        Ok(Self(timer))
+    }
+
+    pub fn index(self) -> u8 {
+        self.0
+    }
+}
+
+// Analog input A0-A7
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AdcChannel(u8);
+
+impl AdcChannel {
+    pub fn new(channel: u8) -> Result<Self> {
+        if channel >= ADC_CHANNELS {
+            bail!(Error::invalid(format!(
+                "ADC CHANNEL A{channel} OUT OF RANGE (A0-A{})",
+                ADC_CHANNELS - 1
+            )));
+        }
+
+        Ok(Self(channel))
+    }
+
+    pub fn index(self) -> u8 {
+        self.0
+    }
+}
+
+// Ordered timers first, so a replay sets each period before the pulse widths that depend on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Register {
+    Prescaler(Timer),
+    Period(Timer),
+    PulseWidth(Channel),
+    Adc(AdcChannel),
+}
+
+impl Register {
+    pub fn address(self) -> u8 {
+        match self {
+            Register::Prescaler(timer) => REG_PSC + timer.0,
+            Register::Period(timer) => REG_PER + timer.0,
+            Register::PulseWidth(channel) => REG_PW + channel.0,
+            Register::Adc(channel) => REG_ADC | (ADC_CHANNELS - 1 - channel.0),
+        }
+    }
+}
+
+// One register write: [register, value high byte, value low byte] on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Command {
+    pub register: Register,
+    pub value: u16,
+}
+
+impl Command {
+    pub fn pulse_width(channel: Channel, pulse_width: u16) -> Self {
+        Self {
+            register: Register::PulseWidth(channel),
+            value: pulse_width,
+        }
+    }
+
+    // Registers hold prescaler - 1 and period - 1, so both must be at least 1
+    pub fn prescaler(timer: Timer, prescaler: u16) -> Result<Self> {
+        if prescaler == 0 {
+            bail!(Error::invalid("PWM PRESCALER MUST BE AT LEAST 1"));
+        }
+
+        Ok(Self {
+            register: Register::Prescaler(timer),
+            value: prescaler - 1,
+        })
+    }
+
+    pub fn period(timer: Timer, period: u16) -> Result<Self> {
+        if period == 0 {
+            bail!(Error::invalid("PWM PERIOD MUST BE AT LEAST 1"));
+        }
+
+        Ok(Self {
+            register: Register::Period(timer),
+            value: period - 1,
+        })
+    }
+
+    // Selects the channel for the next two-byte conversion read
+    pub fn adc_select(channel: AdcChannel) -> Self {
+        Self {
+            register: Register::Adc(channel),
+            value: 0,
+        }
+    }
+
+    pub fn frame(self) -> [u8; 3] {
+        let [high, low] = encode_word(self.value);
+        [self.register.address(), high, low]
+    }
+
+    pub fn send(self, bus: &mut dyn I2cBus) -> Result<()> {
+        // smbus_write_word_swapped puts the high byte on the wire first
+        bus.smbus_write_word_swapped(self.register.address(), self.value)
+            .with_context(|| format!("ROBOT HAT WRITE {:?} FAILED", self.register))
+    }
+}
+
+// The MCU expects words big-endian (high byte first)
+pub fn encode_word(value: u16) -> [u8; 2] {
+    value.to_be_bytes()
+}
+
+pub fn decode_word(bytes: [u8; 2]) -> u16 {
+    u16::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockBus;
+
+    fn p(channel: u8) -> Channel {
+        Channel::new(channel).unwrap()
+    }
+
+    fn t(timer: u8) -> Timer {
+        Timer::new(timer).unwrap()
+    }
+
+    fn a(channel: u8) -> AdcChannel {
+        AdcChannel::new(channel).unwrap()
+    }
+
+    // Expected frames as written by robot-hat's I2C._i2c_write / ADC.read
+    #[test]
+    fn commands_encode_robot_hat_frames() {
+        let table = [
+            (Command::pulse_width(p(0), 0), [0x20, 0x00, 0x00]),
+            (Command::pulse_width(p(2), 307), [0x22, 0x01, 0x33]),
+            (Command::pulse_width(p(13), 0xABCD), [0x2D, 0xAB, 0xCD]),
+            (Command::prescaler(t(0), 351).unwrap(), [0x40, 0x01, 0x5E]),
+            (Command::prescaler(t(3), 1).unwrap(), [0x43, 0x00, 0x00]),
+            (Command::period(t(0), 4095).unwrap(), [0x44, 0x0F, 0xFE]),
+            (Command::period(t(3), 60000).unwrap(), [0x47, 0xEA, 0x5F]),
+            (Command::adc_select(a(0)), [0x17, 0x00, 0x00]),
+            (Command::adc_select(a(4)), [0x13, 0x00, 0x00]),
+            (Command::adc_select(a(7)), [0x10, 0x00, 0x00]),
+        ];
+
+        for (command, frame) in table {
+            assert_eq!(command.frame(), frame, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn channels_map_to_timers() {
+        let table = [
+            (0, 0),
+            (3, 0),
+            (4, 1),
+            (7, 1),
+            (8, 2),
+            (11, 2),
+            (12, 3),
+            (13, 3),
+        ];
+
+        for (channel, timer) in table {
+            assert_eq!(p(channel).timer(), t(timer));
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        assert!(Channel::new(14).is_err());
+        assert!(Timer::new(4).is_err());
+        assert!(AdcChannel::new(8).is_err());
+        assert!(Command::prescaler(t(0), 0).is_err());
+        assert!(Command::period(t(0), 0).is_err());
+    }
+
+    #[test]
+    fn send_puts_high_byte_first() {
+        let mock = MockBus::new();
+        Command::pulse_width(p(5), 0x1234)
+            .send(&mut mock.clone())
+            .unwrap();
+
+        let write = mock.last_word_write().unwrap();
+        assert_eq!(write.register, 0x25);
+        assert_eq!(write.bytes(), [0x12, 0x34]);
+        assert_eq!(decode_word(write.bytes()), 0x1234);
+    }
+}
@@ -0,0 +1,200 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{ops::RangeInclusive, str::FromStr};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    error::{Error, PyError},
+    hal::I2cBus,
+    hat::RobotHat,
+    SLAVE_ADDR,
+};
+
+// 0x00-0x02 and 0x78-0x7F are reserved by the I2C spec (i2cdetect skips them too)
+pub const SCAN_RANGE: RangeInclusive<u16> = 0x03..=0x77;
+
+// Known devices, by 7-bit address
+const KNOWN_DEVICES: [(u16, &str); 5] = [
+    (0x14, "Robot HAT MCU"),
+    (0x15, "Robot HAT MCU (alt)"),
+    (0x16, "Robot HAT MCU (alt)"),
+    (0x3C, "SSD1306 OLED"),
+    (0x68, "MPU6050 IMU"),
+];
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeMode {
+    Auto,       // read byte for EEPROM ranges, quick write elsewhere (as i2cdetect)
+    QuickWrite, // SMBus quick command (write bit)
+    ReadByte,   // SMBus receive byte
+}
+
+impl ProbeMode {
+    fn for_address(self, address: u16) -> ProbeMode {
+        match self {
+            // quick write can corrupt some EEPROMs and read byte can lock write-only chips
+            ProbeMode::Auto if (0x30..=0x37).contains(&address) => ProbeMode::ReadByte,
+            ProbeMode::Auto if (0x50..=0x5F).contains(&address) => ProbeMode::ReadByte,
+            ProbeMode::Auto => ProbeMode::QuickWrite,
+            mode => mode,
+        }
+    }
+}
+
+// "auto", "quick" or "read", as i2cdetect's default, -q and -r
+impl FromStr for ProbeMode {
+    type Err = Error;
+
+    fn from_str(mode: &str) -> Result<Self, Error> {
+        match mode {
+            "auto" => Ok(ProbeMode::Auto),
+            "quick" => Ok(ProbeMode::QuickWrite),
+            "read" => Ok(ProbeMode::ReadByte),
+            _ => Err(Error::invalid(format!(
+                "UNKNOWN I2C PROBE MODE {mode:?} (auto, quick, read)"
+            ))),
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct I2cDevice {
+    #[pyo3(get)]
+    pub address: u16,
+    #[pyo3(get)]
+    pub name: Option<String>,
+}
+
+#[pymethods]
+impl I2cDevice {
+    fn __repr__(&self) -> String {
+        match &self.name {
+            Some(name) => format!("I2cDevice(0x{:02x}, {name})", self.address),
+            None => format!("I2cDevice(0x{:02x})", self.address),
+        }
+    }
+}
+
+pub fn device_name(address: u16) -> Option<&'static str> {
+    KNOWN_DEVICES
+        .iter()
+        .find(|(known, _)| *known == address)
+        .map(|(_, name)| *name)
+}
+
+// Probes every non-reserved address, and leaves the bus pointing at the Robot HAT MCU
+// again, even when the scan fails half way
+pub fn probe_bus(bus: &mut dyn I2cBus, mode: ProbeMode) -> Result<Vec<I2cDevice>> {
+    let mut scan = || -> Result<Vec<I2cDevice>> {
+        let mut devices = vec![];
+        for address in SCAN_RANGE {
+            bus.set_slave_address(address)
+                .with_context(|| format!("I2C SCAN: SETTING ADDRESS 0x{address:02x} FAILED"))?;
+
+            let found = match mode.for_address(address) {
+                ProbeMode::ReadByte => bus.smbus_receive_byte().is_ok(),
+                _ => bus.smbus_quick_command(false).is_ok(),
+            };
+
+            if found {
+                devices.push(I2cDevice {
+                    address,
+                    name: device_name(address).map(String::from),
+                });
+            }
+        }
+
+        Ok(devices)
+    };
+    let devices = scan();
+
+    let restored = bus
+        .set_slave_address(SLAVE_ADDR)
+        .context("I2C SCAN: RESTORING SLAVE ADDRESS FAILED");
+    let devices = devices?;
+    restored?;
+
+    Ok(devices)
+}
+
+#[pyfunction]
+pub fn scan_i2c(mode: Option<ProbeMode>) -> Result<Vec<I2cDevice>, PyError> {
+    RobotHat::shared()?.scan(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockBus;
+
+    #[test]
+    fn scan_reports_known_devices_and_restores_address() {
+        let mock = MockBus::new();
+        mock.add_device(0x14);
+        mock.add_device(0x50);
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+
+        let devices = hat.scan(None).unwrap();
+
+        assert_eq!(
+            devices,
+            vec![
+                I2cDevice {
+                    address: 0x14,
+                    name: Some("Robot HAT MCU".into())
+                },
+                I2cDevice {
+                    address: 0x50,
+                    name: None
+                },
+            ]
+        );
+        assert_eq!(mock.address(), SLAVE_ADDR);
+    }
+
+    #[test]
+    fn failed_scan_still_restores_address() {
+        let mock = MockBus::new();
+        mock.reject_address(0x40);
+
+        assert!(probe_bus(&mut mock.clone(), ProbeMode::Auto).is_err());
+        assert_eq!(mock.address(), SLAVE_ADDR);
+
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        assert!(hat.scan(None).is_err());
+        assert_eq!(mock.address(), SLAVE_ADDR);
+        // neither retried nor recovered by resetting the MCU
+        assert!(hat.take_events().is_empty());
+    }
+
+    #[test]
+    fn scan_skips_reserved_addresses() {
+        let mock = MockBus::new();
+        for address in [0x00, 0x02, 0x03, 0x77, 0x78, 0x7F] {
+            mock.add_device(address);
+        }
+
+        let devices = probe_bus(&mut mock.clone(), ProbeMode::QuickWrite).unwrap();
+        let addresses: Vec<u16> = devices.iter().map(|d| d.address).collect();
+
+        assert_eq!(addresses, vec![0x03, 0x77]);
+    }
+
+    #[test]
+    fn auto_mode_reads_eeprom_ranges() {
+        assert_eq!(ProbeMode::Auto.for_address(0x14), ProbeMode::QuickWrite);
+        assert_eq!(ProbeMode::Auto.for_address(0x33), ProbeMode::ReadByte);
+        assert_eq!(ProbeMode::Auto.for_address(0x57), ProbeMode::ReadByte);
+        assert_eq!(
+            ProbeMode::QuickWrite.for_address(0x57),
+            ProbeMode::QuickWrite
+        );
+        assert_eq!("read".parse::<ProbeMode>().unwrap(), ProbeMode::ReadByte);
+        assert!("i2cdetect".parse::<ProbeMode>().is_err());
+    }
+}
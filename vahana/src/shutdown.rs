@@ -0,0 +1,145 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use anyhow::{Context, Result};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+    low_level::emulate_default_handler,
+};
+
+use crate::{error::Error, hat::RobotHat, protocol::Command};
+
+// Safe-state write of every live actuator, e.g. zero duty for a motor channel
+static PARKED: Mutex<BTreeMap<u64, (RobotHat, Command)>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+fn parked() -> MutexGuard<'static, BTreeMap<u64, (RobotHat, Command)>> {
+    PARKED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Keeps `command` registered for park_all() until dropped
+pub struct ParkGuard {
+    id: u64,
+}
+
+impl ParkGuard {
+    pub fn register(hat: RobotHat, command: Command) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        parked().insert(id, (hat, command));
+
+        Self { id }
+    }
+
+    // Replaces the safe-state write, e.g. after a new servo park angle
+    pub fn update(&self, command: Command) {
+        if let Some((_, parked)) = parked().get_mut(&self.id) {
+            *parked = command;
+        }
+    }
+}
+
+impl Drop for ParkGuard {
+    fn drop(&mut self) {
+        parked().remove(&self.id);
+    }
+}
+
+// Sends every registered safe-state write, motors first, and reports the first failure
+pub fn park_all() -> Result<()> {
+    park(|_| true)
+}
+
+// Only the actuators on `hat`
+pub fn park_hat(hat: &RobotHat) -> Result<()> {
+    park(|parked| parked.ptr_eq(hat))
+}
+
+fn park(filter: impl Fn(&RobotHat) -> bool) -> Result<()> {
+    let mut targets: Vec<(RobotHat, Command)> = parked()
+        .values()
+        .filter(|(hat, _)| filter(hat))
+        .cloned()
+        .collect();
+    targets.sort_by_key(|(_, command)| command.value != 0);
+
+    let mut result = Ok(());
+    for (hat, command) in targets {
+        let sent = hat.lock().send(command).with_context(|| {
+            format!(
+                "PARKING REGISTER 0x{:02x} FAILED",
+                command.register.address()
+            )
+        });
+        result = result.and(sent);
+    }
+
+    result
+}
+
+// Parks everything on SIGINT / SIGTERM. SIGTERM then terminates the process as it would
+// have. SIGINT is left to the handler installed before us (chained by signal-hook), which
+// in Python raises KeyboardInterrupt so `finally:` blocks still run
+pub fn install() -> Result<()> {
+    if INSTALLED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let mut signals = Signals::new([SIGINT, SIGTERM])
+        .map_err(Error::from)
+        .context("SHUTDOWN SIGNAL HANDLER INIT FAILED")?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if let Err(error) = park_all() {
+                eprintln!("{error:#}");
+            }
+            if signal == SIGTERM {
+                let _ = emulate_default_handler(signal);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock::MockBus, protocol::Channel};
+
+    #[test]
+    fn registered_commands_are_parked_until_dropped() {
+        let mock = MockBus::new();
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let servo = ParkGuard::register(
+            hat.clone(),
+            Command::pulse_width(Channel::new(9).unwrap(), 4500),
+        );
+        let motor = ParkGuard::register(
+            hat.clone(),
+            Command::pulse_width(Channel::new(10).unwrap(), 0),
+        );
+        servo.update(Command::pulse_width(Channel::new(9).unwrap(), 3000));
+
+        mock.clear();
+        park_hat(&hat).unwrap();
+        let writes: Vec<(u8, u16)> = mock
+            .word_writes()
+            .iter()
+            .map(|w| (w.register, w.mcu_value()))
+            .collect();
+        assert_eq!(writes, vec![(0x2A, 0), (0x29, 3000)]);
+
+        drop((servo, motor));
+        mock.clear();
+        park_hat(&hat).unwrap();
+        assert!(mock.word_writes().is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+use crate::error::Error;
+
+pub const CLOCK: u32 = 72_000_000; // Robot HAT MCU timer clock
+pub const MAX_PRESCALER: u16 = u16::MAX;
+pub const MAX_PERIOD: u16 = u16::MAX;
+// at least 1% duty steps, pulse_width_percent is integer percent
+pub const MIN_PERIOD: u16 = 100;
+
+pub const MIN_FREQ: f64 = CLOCK as f64 / (MAX_PRESCALER as f64 * MAX_PERIOD as f64);
+pub const MAX_FREQ: f64 = CLOCK as f64 / MIN_PERIOD as f64;
+
+// Seconds from Python as a Duration, `what` names the value in the error
+pub fn seconds(what: &str, seconds: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| Error::invalid(format!("{what} {seconds} S OUT OF RANGE")).into())
+}
+
+// freq = CLOCK / (prescaler * period), both as counts (registers hold value - 1)
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PwmTiming {
+    #[pyo3(get)]
+    pub prescaler: u16,
+    #[pyo3(get)]
+    pub period: u16,
+    #[pyo3(get)]
+    pub frequency: f64, // achieved, Hz
+}
+
+#[pymethods]
+impl PwmTiming {
+    // Duty cycle step in percent
+    #[getter]
+    pub fn resolution(&self) -> f64 {
+        100.0 / self.period as f64
+    }
+
+    // Requested minus achieved, Hz
+    pub fn error(&self, freq: f64) -> f64 {
+        freq - self.frequency
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PwmTiming(prescaler={}, period={}, frequency={})",
+            self.prescaler, self.period, self.frequency
+        )
+    }
+}
+
+impl PwmTiming {
+    fn new(prescaler: u16, period: u16) -> Self {
+        let frequency = CLOCK as f64 / (prescaler as f64 * period as f64);
+        Self {
+            prescaler,
+            period,
+            frequency,
+        }
+    }
+
+    // Prescaler/period pair with the smallest frequency error; on ties the
+    // longest period (finest duty resolution) wins
+    pub fn solve(freq: f64) -> Result<Self> {
+        if !freq.is_finite() || freq <= 0.0 {
+            bail!(Error::invalid(format!(
+                "PWM FREQUENCY {freq} Hz IS NOT A POSITIVE NUMBER"
+            )));
+        }
+        if !(MIN_FREQ..=MAX_FREQ).contains(&freq) {
+            bail!(Error::invalid(format!(
+                "PWM FREQUENCY {freq} Hz OUT OF RANGE ({MIN_FREQ:.4} - {MAX_FREQ} Hz)"
+            )));
+        }
+
+        let ticks = CLOCK as f64 / freq; // prescaler * period
+        let mut best: Option<(f64, PwmTiming)> = None;
+
+        for prescaler in 1..=MAX_PRESCALER {
+            let ideal = ticks / prescaler as f64;
+            if ideal.ceil() < MIN_PERIOD as f64 {
+                break;
+            }
+            if ideal.floor() > MAX_PERIOD as f64 {
+                continue;
+            }
+
+            for period in [ideal.floor(), ideal.ceil()] {
+                if !(MIN_PERIOD as f64..=MAX_PERIOD as f64).contains(&period) {
+                    continue;
+                }
+
+                let timing = PwmTiming::new(prescaler, period as u16);
+                let error = timing.error(freq).abs();
+                // strictly better only, earlier prescalers have longer periods
+                if best.is_none_or(|(best_error, _)| error < best_error * (1.0 - 1e-12)) {
+                    best = Some((error, timing));
+                }
+            }
+
+            if best.is_some_and(|(error, _)| error == 0.0) {
+                break;
+            }
+        }
+
+        match best {
+            Some((_, timing)) => Ok(timing),
+            None => bail!(Error::invalid(format!(
+                "PWM FREQUENCY {freq} Hz NOT REACHABLE"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_frequencies_prefer_fine_resolution() {
+        for (freq, prescaler, period) in [(50.0, 24, 60000), (100.0, 12, 60000), (1000.0, 2, 36000)]
+        {
+            let timing = PwmTiming::solve(freq).unwrap();
+            assert_eq!((timing.prescaler, timing.period), (prescaler, period));
+        }
+    }
+
+    #[test]
+    fn solver_minimises_frequency_error() {
+        for freq in [
+            0.5, 33.3, 50.0, 333.0, 1758.0, 14_400.0, 123_456.0, 700_000.0,
+        ] {
+            let timing = PwmTiming::solve(freq).unwrap();
+            assert!(timing.period >= MIN_PERIOD);
+            // within half a period count of the ideal
+            let relative_error = timing.error(freq).abs() / freq;
+            assert!(
+                relative_error < 1.0 / timing.period as f64,
+                "{freq}: {timing:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unreachable_frequencies_are_rejected() {
+        for freq in [0.0, -50.0, f64::NAN, f64::INFINITY, 0.01, MAX_FREQ * 1.01] {
+            assert!(PwmTiming::solve(freq).is_err(), "{freq}");
+        }
+        assert!(PwmTiming::solve(MAX_FREQ).is_ok());
+    }
+}
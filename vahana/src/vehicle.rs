@@ -0,0 +1,401 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    board,
+    drive::{Motors, Servo, MOTOR_FREQ, SERVO_FREQ},
+    error::{Error, PyError},
+    hat::RobotHat,
+    layout,
+    odometry::{Odometry, OdometryConfig},
+    watchdog::WatchdogStatus,
+};
+
+// Signs follow the steering servo: a positive angle or curvature turns right
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VehicleGeometry {
+    #[pyo3(get, set)]
+    pub wheelbase: f64, // front to rear axle (m)
+    #[pyo3(get, set)]
+    pub track_width: f64, // between the rear wheels (m)
+    #[pyo3(get, set)]
+    pub max_steering: f64, // steering servo limit (deg)
+}
+
+// PiCar-X chassis (ref: picar-x DIR_MIN / DIR_MAX)
+impl Default for VehicleGeometry {
+    fn default() -> Self {
+        Self {
+            wheelbase: 0.095,
+            track_width: 0.11,
+            max_steering: 30.0,
+        }
+    }
+}
+
+#[pymethods]
+impl VehicleGeometry {
+    #[new]
+    #[pyo3(signature = (wheelbase = 0.095, track_width = 0.11, max_steering = 30.0))]
+    pub fn new(wheelbase: f64, track_width: f64, max_steering: f64) -> Self {
+        Self {
+            wheelbase,
+            track_width,
+            max_steering,
+        }
+    }
+
+    // Front wheel angle (deg) for a path of `curvature` (1/m), within the servo limit
+    pub fn steering_angle(&self, curvature: f64) -> f64 {
+        (self.wheelbase * curvature)
+            .atan()
+            .to_degrees()
+            .clamp(-self.max_steering, self.max_steering)
+    }
+
+    pub fn curvature(&self, steering_angle: f64) -> f64 {
+        steering_angle
+            .clamp(-self.max_steering, self.max_steering)
+            .to_radians()
+            .tan()
+            / self.wheelbase
+    }
+
+    // Servo angle and rear wheel speeds for `speed` (-100..100) along `curvature`
+    pub fn mix(&self, speed: f64, curvature: f64) -> AckermannMix {
+        self.mix_angle(speed, self.steering_angle(curvature))
+    }
+
+    pub fn mix_angle(&self, speed: f64, steering_angle: f64) -> AckermannMix {
+        let steering = steering_angle.clamp(-self.max_steering, self.max_steering);
+        let curvature = self.curvature(steering);
+
+        // inner wheel slows down, outer speeds up; both scaled back if the outer one saturates
+        let half_track = curvature * self.track_width / 2.0;
+        let (mut left, mut right) = (speed * (1.0 + half_track), speed * (1.0 - half_track));
+        let fastest = left.abs().max(right.abs());
+        if fastest > 100.0 {
+            left *= 100.0 / fastest;
+            right *= 100.0 / fastest;
+        }
+
+        AckermannMix {
+            steering,
+            left,
+            right,
+        }
+    }
+}
+
+impl VehicleGeometry {
+    fn validate(&self) -> Result<()> {
+        if !(self.wheelbase > 0.0 && self.track_width > 0.0) {
+            bail!(Error::invalid(format!(
+                "VEHICLE WHEELBASE {} AND TRACK WIDTH {} MUST BE POSITIVE",
+                self.wheelbase, self.track_width
+            )));
+        }
+        if !(self.max_steering > 0.0 && self.max_steering < 90.0) {
+            bail!(Error::invalid(format!(
+                "VEHICLE MAX STEERING {} OUT OF RANGE (0, 90)",
+                self.max_steering
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AckermannMix {
+    #[pyo3(get)]
+    pub steering: f64, // servo angle (deg)
+    #[pyo3(get)]
+    pub left: f64, // rear wheel speeds (-100..100)
+    #[pyo3(get)]
+    pub right: f64,
+}
+
+#[pymethods]
+impl AckermannMix {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+// Front wheel steering plus rear differential, driven together. Clones share
+// the motors and servo but keep their own geometry
+#[pyclass]
+#[derive(Clone)]
+pub struct Vehicle {
+    motors: Motors,
+    steering: Servo,
+    geometry: VehicleGeometry,
+}
+
+impl Vehicle {
+    pub fn with_parts(motors: Motors, steering: Servo, geometry: VehicleGeometry) -> Result<Self> {
+        geometry.validate()?;
+
+        Ok(Self {
+            motors,
+            steering,
+            geometry,
+        })
+    }
+
+    pub fn with_hat(hat: &RobotHat, freq: f64) -> Result<Self> {
+        let pins = board::current()?.pins;
+        let steering = pins.pwm(&layout::current().servos.steering)?;
+        let motors = Motors::with_hat(hat, freq).context("MOTORS INIT FAILED")?;
+        let steering = Servo::with_pwm(hat.pwm(steering.index())?, SERVO_FREQ)
+            .context("STEERING SERVO INIT FAILED")?;
+
+        Vehicle::with_parts(motors, steering, VehicleGeometry::default())
+    }
+
+    pub fn apply(&mut self, mix: AckermannMix) -> Result<()> {
+        self.steering
+            .angle(mix.steering.round() as i32)
+            .context("STEERING FAILED")?;
+        self.motors.command(mix.left, mix.right)
+    }
+}
+
+#[pymethods]
+impl Vehicle {
+    #[new]
+    #[pyo3(signature = (freq = MOTOR_FREQ))]
+    pub fn new(freq: f64) -> Result<Self, PyError> {
+        let hat = RobotHat::shared().context("PWM I2C INIT FAILED")?;
+        Ok(Vehicle::with_hat(&hat, freq)?)
+    }
+
+    // `curvature` in 1/m, positive to the right
+    pub fn drive(&mut self, speed: f64, curvature: f64) -> Result<AckermannMix, PyError> {
+        let mix = self.geometry.mix(speed, curvature);
+        self.apply(mix)?;
+
+        Ok(mix)
+    }
+
+    // `angle` in degrees at the front wheels, positive to the right
+    pub fn drive_angle(&mut self, speed: f64, angle: f64) -> Result<AckermannMix, PyError> {
+        let mix = self.geometry.mix_angle(speed, angle);
+        self.apply(mix)?;
+
+        Ok(mix)
+    }
+
+    // Ramped stop, steering left where it is
+    pub fn stop(&self) -> Result<(), PyError> {
+        self.motors.stop()
+    }
+
+    pub fn emergency_stop(&self) -> Result<(), PyError> {
+        self.motors.emergency_stop()
+    }
+
+    // Motors to zero now, steering to its park angle
+    pub fn park(&self) -> Result<(), PyError> {
+        let stopped = self.motors.emergency_stop();
+        let parked = self.steering.park();
+
+        stopped.and(parked)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> Result<bool, PyError> {
+        self.park()?;
+        Ok(false)
+    }
+
+    // Stops the motors and centres the steering when no drive command arrives for `timeout_ms`
+    #[pyo3(signature = (timeout_ms = 500))]
+    pub fn enable_watchdog(&self, timeout_ms: u64) -> Result<(), PyError> {
+        let steering = self.steering.clone();
+        self.motors
+            .watch(Duration::from_millis(timeout_ms), move || {
+                steering.lock().angle(0).context("STEERING CENTRE FAILED")
+            })?;
+
+        Ok(())
+    }
+
+    pub fn disable_watchdog(&self) {
+        self.motors.disable_watchdog();
+    }
+
+    #[getter]
+    pub fn watchdog(&self) -> Option<WatchdogStatus> {
+        self.motors.watchdog()
+    }
+
+    // Starts dead reckoning at the origin from the motor and steering commands
+    #[pyo3(signature = (config = None))]
+    pub fn enable_odometry(&self, config: Option<OdometryConfig>) -> Result<Odometry, PyError> {
+        let odometry = Odometry::with_config(config.unwrap_or_default(), self.geometry.wheelbase)?;
+        let (left, right) = self.motors.output();
+        let steering = self.steering.lock().commanded().unwrap_or(0);
+        odometry.command((left + right) / 2.0, steering as f64);
+        self.motors.set_odometry(Some(odometry.clone()));
+        self.steering.set_odometry(Some(odometry.clone()));
+
+        Ok(odometry)
+    }
+
+    pub fn disable_odometry(&self) {
+        self.motors.set_odometry(None);
+        self.steering.set_odometry(None);
+    }
+
+    #[getter]
+    pub fn odometry(&self) -> Option<Odometry> {
+        self.motors.odometry()
+    }
+
+    #[getter]
+    pub fn motors(&self) -> Motors {
+        self.motors.clone()
+    }
+
+    #[getter]
+    pub fn steering(&self) -> Servo {
+        self.steering.clone()
+    }
+
+    #[getter]
+    pub fn geometry(&self) -> VehicleGeometry {
+        self.geometry
+    }
+
+    #[setter]
+    pub fn set_geometry(&mut self, geometry: VehicleGeometry) -> Result<(), PyError> {
+        geometry.validate()?;
+        self.geometry = geometry;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        drive::Motor,
+        mock::{MockBus, MockPin},
+    };
+
+    use rppal::gpio::Level;
+
+    fn mock_vehicle(mock: &MockBus) -> (Vehicle, MockPin, MockPin) {
+        let hat = RobotHat::with_bus(Box::new(mock.clone())).unwrap();
+        let (left_pin, right_pin) = (MockPin::new(), MockPin::new());
+        let left = Motor::with_parts(hat.pwm(12).unwrap(), Box::new(left_pin.clone()), 50.0);
+        let right = Motor::with_parts(hat.pwm(13).unwrap(), Box::new(right_pin.clone()), 50.0);
+        let motors = Motors::with_motors(left.unwrap(), right.unwrap());
+        let steering = Servo::with_pwm(hat.pwm(2).unwrap(), SERVO_FREQ).unwrap();
+        let vehicle = Vehicle::with_parts(motors, steering, VehicleGeometry::default()).unwrap();
+
+        (vehicle, left_pin, right_pin)
+    }
+
+    #[test]
+    fn straight_line_centres_steering() {
+        let mix = VehicleGeometry::default().mix(60.0, 0.0);
+
+        assert_eq!(mix.steering, 0.0);
+        assert_eq!((mix.left, mix.right), (60.0, 60.0));
+    }
+
+    #[test]
+    fn inner_wheel_slows_in_proportion() {
+        let geometry = VehicleGeometry::default();
+
+        // 20 deg right: kappa = tan(20 deg) / 0.095
+        let right = geometry.mix(50.0, geometry.curvature(20.0));
+        assert!((right.steering - 20.0).abs() < 1e-9);
+        let kappa = 20f64.to_radians().tan() / 0.095;
+        assert!((right.left - 50.0 * (1.0 + kappa * 0.055)).abs() < 1e-9);
+        assert!((right.right - 50.0 * (1.0 - kappa * 0.055)).abs() < 1e-9);
+
+        let left = geometry.mix(50.0, -geometry.curvature(20.0));
+        assert!((left.steering + 20.0).abs() < 1e-9);
+        assert!((left.left - right.right).abs() < 1e-9);
+    }
+
+    #[test]
+    fn steering_and_outer_wheel_saturate() {
+        let geometry = VehicleGeometry::default();
+        let mix = geometry.mix(100.0, 50.0); // tighter than the servo allows
+
+        assert_eq!(mix.steering, 30.0);
+        assert_eq!(mix.left, 100.0);
+        let kappa = geometry.curvature(30.0);
+        let ratio = (1.0 - kappa * 0.055) / (1.0 + kappa * 0.055);
+        assert!((mix.right - 100.0 * ratio).abs() < 1e-9);
+    }
+
+    #[test]
+    fn drive_writes_servo_and_both_motors() {
+        let mock = MockBus::new();
+        let (mut vehicle, left_pin, right_pin) = mock_vehicle(&mock);
+        mock.clear();
+
+        let mix = vehicle.drive_angle(40.0, -90.0).unwrap();
+        assert_eq!(mix.steering, -30.0);
+
+        let writes: Vec<(u8, u16)> = mock
+            .word_writes()
+            .iter()
+            .map(|w| (w.register, w.mcu_value()))
+            .collect();
+        assert_eq!(writes[0], (0x22, 3498)); // -30 deg -> 1166 us
+        assert_eq!(writes[1].0, 0x2C);
+        assert_eq!(writes[2].0, 0x2D);
+        assert!(writes[1].1 < writes[2].1); // left is the inner wheel
+        assert_eq!(left_pin.level(), Some(Level::High));
+        assert_eq!(right_pin.level(), Some(Level::Low)); // inverted right motor
+
+        assert!(vehicle
+            .set_geometry(VehicleGeometry::new(0.0, 0.11, 30.0))
+            .is_err());
+    }
+
+    #[test]
+    fn watchdog_stops_and_centres_when_commands_stop() {
+        let mock = MockBus::new();
+        let (mut vehicle, _, _) = mock_vehicle(&mock);
+        vehicle.enable_watchdog(40).unwrap();
+
+        vehicle.drive_angle(50.0, 20.0).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+
+        let status = vehicle.watchdog().unwrap();
+        assert!(status.tripped);
+        assert_eq!(status.error, None);
+        assert_eq!(vehicle.motors().output(), (0.0, 0.0));
+        let last = mock.last_word_write().unwrap();
+        assert_eq!((last.register, last.mcu_value()), (0x22, 4500));
+
+        vehicle.drive(30.0, 0.0).unwrap();
+        assert!(!vehicle.watchdog().unwrap().tripped);
+        vehicle.disable_watchdog();
+        assert!(vehicle.watchdog().is_none());
+    }
+}
@@ -0,0 +1,204 @@
+// rustimport:pyo3
+
+use pyo3::prelude::*;
+
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+
+use crate::error::Error;
+
+type ExpireFn = Box<dyn FnMut() -> Result<()> + Send>;
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogStatus {
+    #[pyo3(get)]
+    pub timeout_ms: u64,
+    #[pyo3(get)]
+    pub remaining_ms: Option<u64>, // None once tripped, until the next command
+    #[pyo3(get)]
+    pub tripped: bool,
+    #[pyo3(get)]
+    pub trips: u32,
+    #[pyo3(get)]
+    pub error: Option<String>, // failure of the last safe-state action
+}
+
+#[pymethods]
+impl WatchdogStatus {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+struct State {
+    timeout: Duration,
+    deadline: Option<Instant>,
+    running: bool,
+    tripped: bool,
+    trips: u32,
+    error: Option<String>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Runs `on_expire` on its own thread when feed() is not called within the timeout.
+// Armed from creation; stops when dropped
+pub struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+impl Watchdog {
+    pub fn spawn(
+        timeout: Duration,
+        on_expire: impl FnMut() -> Result<()> + Send + 'static,
+    ) -> Result<Self> {
+        if timeout.is_zero() {
+            bail!(Error::invalid("WATCHDOG TIMEOUT MUST BE ABOVE 0"));
+        }
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                timeout,
+                deadline: Some(Instant::now() + timeout),
+                running: true,
+                tripped: false,
+                trips: 0,
+                error: None,
+            }),
+            wake: Condvar::new(),
+        });
+        let watched = shared.clone();
+        thread::spawn(move || run(&watched, Box::new(on_expire)));
+
+        Ok(Self { shared })
+    }
+
+    // Pushes the deadline out by one timeout and re-arms a tripped watchdog
+    pub fn feed(&self) {
+        let mut state = self.shared.lock();
+        state.deadline = Some(Instant::now() + state.timeout);
+        state.tripped = false;
+        self.shared.wake.notify_one();
+    }
+
+    pub fn status(&self) -> WatchdogStatus {
+        let state = self.shared.lock();
+        let now = Instant::now();
+        WatchdogStatus {
+            timeout_ms: state.timeout.as_millis() as u64,
+            remaining_ms: state
+                .deadline
+                .map(|deadline| deadline.saturating_duration_since(now).as_millis() as u64),
+            tripped: state.tripped,
+            trips: state.trips,
+            error: state.error.clone(),
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.lock().running = false;
+        self.shared.wake.notify_one();
+    }
+}
+
+fn run(shared: &Shared, mut on_expire: ExpireFn) {
+    let mut state = shared.lock();
+    while state.running {
+        let now = Instant::now();
+        match state.deadline {
+            None => {
+                state = shared.wake.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+            Some(deadline) if now < deadline => {
+                state = shared
+                    .wake
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+            Some(_) => {
+                state.deadline = None;
+                // the action takes the actuator locks, never hold ours meanwhile
+                drop(state);
+                let result = on_expire();
+                state = shared.lock();
+                state.tripped = true;
+                state.trips += 1;
+                state.error = result.err().map(|error| format!("{error:#}"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        thread::sleep,
+    };
+
+    #[test]
+    fn expires_once_without_feeding() {
+        let fired = Arc::new(AtomicU32::new(0));
+        let counter = fired.clone();
+        let watchdog = Watchdog::spawn(Duration::from_millis(20), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .unwrap();
+
+        sleep(Duration::from_millis(100));
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        let status = watchdog.status();
+        assert!(status.tripped);
+        assert_eq!(status.remaining_ms, None);
+
+        watchdog.feed();
+        assert!(!watchdog.status().tripped);
+        sleep(Duration::from_millis(100));
+        assert_eq!(fired.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn feeding_holds_it_off() {
+        let fired = Arc::new(AtomicU32::new(0));
+        let counter = fired.clone();
+        let watchdog = Watchdog::spawn(Duration::from_millis(80), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            bail!("PARK FAILED")
+        })
+        .unwrap();
+
+        for _ in 0..5 {
+            sleep(Duration::from_millis(20));
+            watchdog.feed();
+        }
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+        assert!(watchdog.status().remaining_ms.is_some());
+
+        sleep(Duration::from_millis(200));
+        let status = watchdog.status();
+        assert_eq!(status.trips, 1);
+        assert_eq!(status.error.as_deref(), Some("PARK FAILED"));
+        assert!(Watchdog::spawn(Duration::ZERO, || Ok(())).is_err());
+    }
+}